@@ -0,0 +1,788 @@
+use hashbrown::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use flate2::read::MultiGzDecoder;
+
+use crate::error::SkaloError;
+use crate::utils::{progress, Contig};
+
+// structure to store a coding sequence of the reference genome
+#[derive(Clone, Debug)]
+pub struct Gene {
+    pub contig: usize,
+    // 1-based inclusive coordinates of the CDS segments, in increasing order
+    pub segments: Vec<(u32, u32)>,
+    pub forward: bool,
+    // number of bases before the first complete codon (GFF3 phase of the first segment, or GenBank codon_start - 1)
+    pub phase: u32,
+    pub name: String,
+    pub locus_tag: String,
+}
+
+impl Gene {
+    fn start(&self) -> u32 {
+        self.segments[0].0
+    }
+
+    fn end(&self) -> u32 {
+        self.segments[self.segments.len() - 1].1
+    }
+
+    // length of the CDS from its first complete codon
+    fn cds_length(&self) -> u32 {
+        self.segments
+            .iter()
+            .map(|(start, end)| end - start + 1)
+            .sum::<u32>()
+            .saturating_sub(self.phase)
+    }
+
+    // offset in the CDS (0-based from the first complete codon, in the orientation of the gene) of a 1-based
+    // genomic position (None if outside the CDS or before its first complete codon)
+    fn cds_offset(&self, position: u32) -> Option<u32> {
+        let mut offset = 0;
+        let segments: Vec<&(u32, u32)> = if self.forward {
+            self.segments.iter().collect()
+        } else {
+            self.segments.iter().rev().collect()
+        };
+        for &(start, end) in segments {
+            if position >= start && position <= end {
+                return (offset
+                    + if self.forward {
+                        position - start
+                    } else {
+                        end - position
+                    })
+                .checked_sub(self.phase);
+            }
+            offset += end - start + 1;
+        }
+        None
+    }
+
+    // 1-based genomic position of an offset in the CDS (from its first complete codon)
+    fn genomic_position(&self, cds_offset: u32) -> Option<u32> {
+        let mut remaining = cds_offset + self.phase;
+        let segments: Vec<&(u32, u32)> = if self.forward {
+            self.segments.iter().collect()
+        } else {
+            self.segments.iter().rev().collect()
+        };
+        for &(start, end) in segments {
+            let length = end - start + 1;
+            if remaining < length {
+                return Some(if self.forward {
+                    start + remaining
+                } else {
+                    end - remaining
+                });
+            }
+            remaining -= length;
+        }
+        None
+    }
+}
+
+// CDS of the reference genome indexed by contig and sorted by start, to find the CDS overlapping a position
+#[derive(Clone, Debug, Default)]
+pub struct GeneIndex {
+    // CDS of each contig, and the longest span of a CDS of the contig
+    contigs: Vec<(Vec<Gene>, u32)>,
+}
+
+impl GeneIndex {
+    pub fn new(genes: Vec<Gene>, nb_contigs: usize) -> Self {
+        let mut contigs: Vec<(Vec<Gene>, u32)> = vec![(Vec::new(), 0); nb_contigs];
+        for gene in genes {
+            let (contig_genes, max_span) = &mut contigs[gene.contig];
+            *max_span = (*max_span).max(gene.end() - gene.start() + 1);
+            contig_genes.push(gene);
+        }
+        for (contig_genes, _) in contigs.iter_mut() {
+            contig_genes.sort_by_key(|gene| (gene.start(), gene.end()));
+        }
+        Self { contigs }
+    }
+
+    // number of CDS
+    pub fn len(&self) -> usize {
+        self.contigs.iter().map(|(genes, _)| genes.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // CDS of all the contigs
+    pub fn genes(&self) -> impl Iterator<Item = &Gene> {
+        self.contigs.iter().flat_map(|(genes, _)| genes)
+    }
+
+    // CDS whose span contains a 1-based position of a contig (only the CDS starting less than the longest span
+    // before the position are checked)
+    fn overlapping(&self, contig: usize, position: u32) -> impl Iterator<Item = &Gene> {
+        let (genes, max_span) = self
+            .contigs
+            .get(contig)
+            .map_or((&[][..], 0), |(genes, max_span)| {
+                (genes.as_slice(), *max_span)
+            });
+        let first = genes.partition_point(|gene| gene.start() + max_span <= position);
+        let last = genes.partition_point(|gene| gene.start() <= position);
+        genes[first..last]
+            .iter()
+            .filter(move |gene| gene.end() >= position)
+    }
+}
+
+// read the CDS of the reference genome from a GFF3 or a GenBank file (sequence names have to match the contig names)
+pub fn read_annotation(
+    file_path: &Path,
+    contigs: &[Contig],
+    verbose: bool,
+) -> Result<GeneIndex, SkaloError> {
+    let file = File::open(file_path)
+        .map_err(|e| SkaloError::Annotation(format!("{}: {}", file_path.display(), e)))?;
+    let reader: Box<dyn BufRead> = if file_path.to_string_lossy().ends_with(".gz") {
+        Box::new(BufReader::new(MultiGzDecoder::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+    let lines: Vec<String> = reader
+        .lines()
+        .collect::<Result<_, _>>()
+        .map_err(|e| SkaloError::Annotation(format!("{}: {}", file_path.display(), e)))?;
+
+    let contig_indexes: HashMap<&str, usize> = contigs
+        .iter()
+        .enumerate()
+        .map(|(i, contig)| (contig.name.as_str(), i))
+        .collect();
+
+    // GenBank files start with a LOCUS line, otherwise GFF3
+    let is_genbank = lines
+        .iter()
+        .find(|line| !line.trim().is_empty())
+        .is_some_and(|line| line.starts_with("LOCUS"));
+    let (genes, nb_ignored) = if is_genbank {
+        parse_genbank(&lines, &contig_indexes)
+    } else {
+        parse_gff3(&lines, &contig_indexes)
+    };
+
+    if genes.is_empty() {
+        return Err(SkaloError::Annotation(format!(
+            "{}: no CDS found on the contigs of the reference genome",
+            file_path.display()
+        )));
+    }
+    if nb_ignored > 0 {
+        progress!(
+            verbose,
+            "     . {} CDS ignored (sequence name not in the reference genome)",
+            nb_ignored
+        );
+    }
+
+    Ok(GeneIndex::new(genes, contigs.len()))
+}
+
+// CDS of a GFF3 file: lines of the same CDS (same ID) are joined
+fn parse_gff3(lines: &[String], contig_indexes: &HashMap<&str, usize>) -> (Vec<Gene>, usize) {
+    let mut genes: Vec<Gene> = Vec::new();
+    let mut gene_ids: HashMap<String, usize> = HashMap::new();
+    let mut nb_ignored = 0;
+
+    for line in lines {
+        // sequences can be appended at the end of the file
+        if line.starts_with("##FASTA") {
+            break;
+        }
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 9 || fields[2] != "CDS" {
+            continue;
+        }
+        let (Ok(start), Ok(end)) = (fields[3].parse::<u32>(), fields[4].parse::<u32>()) else {
+            continue;
+        };
+        let Some(&contig) = contig_indexes.get(fields[0]) else {
+            nb_ignored += 1;
+            continue;
+        };
+
+        let attributes: HashMap<&str, &str> = fields[8]
+            .split(';')
+            .filter_map(|attribute| attribute.split_once('='))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .collect();
+        let locus_tag = attributes.get("locus_tag").copied().unwrap_or("");
+        let name = ["gene", "Name"]
+            .iter()
+            .find_map(|key| attributes.get(key))
+            .copied()
+            .unwrap_or(locus_tag);
+        let forward = fields[6] != "-";
+        // phase ('.' if unknown)
+        let phase = fields[7].parse::<u32>().unwrap_or(0) % 3;
+
+        // additional segment of a CDS already found (the phase of the CDS is the phase of its first segment)
+        let id = attributes
+            .get("ID")
+            .map(|id| format!("{}|{}", fields[0], id));
+        if let Some(&index) = id.as_ref().and_then(|id| gene_ids.get(id)) {
+            let gene: &mut Gene = &mut genes[index];
+            if (gene.forward && start < gene.start()) || (!gene.forward && end > gene.end()) {
+                gene.phase = phase;
+            }
+            gene.segments.push((start, end));
+            gene.segments.sort_unstable();
+            continue;
+        }
+        if let Some(id) = id {
+            gene_ids.insert(id, genes.len());
+        }
+
+        genes.push(Gene {
+            contig,
+            segments: vec![(start, end)],
+            forward,
+            phase,
+            name: name.to_string(),
+            locus_tag: locus_tag.to_string(),
+        });
+    }
+    (genes, nb_ignored)
+}
+
+// CDS of a GenBank file (one or more records, identified by their LOCUS name or accession)
+fn parse_genbank(lines: &[String], contig_indexes: &HashMap<&str, usize>) -> (Vec<Gene>, usize) {
+    let mut genes: Vec<Gene> = Vec::new();
+    let mut nb_ignored = 0;
+
+    let mut contig: Option<usize> = None;
+    let mut in_features = false;
+    // location and qualifiers of the current feature (None if not a CDS)
+    let mut current_cds: Option<(String, Vec<String>)> = None;
+
+    for line in lines {
+        if line.starts_with("LOCUS") {
+            contig = line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|name| contig_indexes.get(name).copied());
+        } else if line.starts_with("ACCESSION") || line.starts_with("VERSION") {
+            if contig.is_none() {
+                contig = line
+                    .split_whitespace()
+                    .nth(1)
+                    .and_then(|name| contig_indexes.get(name).copied());
+            }
+        } else if line.starts_with("FEATURES") {
+            in_features = true;
+        } else if line.starts_with("ORIGIN") || line.starts_with("//") {
+            if let Some(cds) = current_cds.take() {
+                add_genbank_cds(&mut genes, &mut nb_ignored, contig, cds);
+            }
+            in_features = false;
+        } else if let (true, Some(key), Some(value)) = (in_features, line.get(..21), line.get(21..))
+        {
+            let (key, value) = (key.trim(), value.trim());
+            if !key.is_empty() {
+                // new feature
+                if let Some(cds) = current_cds.take() {
+                    add_genbank_cds(&mut genes, &mut nb_ignored, contig, cds);
+                }
+                if key == "CDS" {
+                    current_cds = Some((value.to_string(), Vec::new()));
+                }
+            } else if let Some((location, qualifiers)) = current_cds.as_mut() {
+                if value.starts_with('/') {
+                    qualifiers.push(value.to_string());
+                } else if qualifiers.is_empty() {
+                    // location spanning several lines
+                    location.push_str(value);
+                } else if let Some(qualifier) = qualifiers.last_mut() {
+                    qualifier.push(' ');
+                    qualifier.push_str(value);
+                }
+            }
+        }
+    }
+    (genes, nb_ignored)
+}
+
+fn add_genbank_cds(
+    genes: &mut Vec<Gene>,
+    nb_ignored: &mut usize,
+    contig: Option<usize>,
+    cds: (String, Vec<String>),
+) {
+    let (location, qualifiers) = cds;
+    let Some(contig) = contig else {
+        *nb_ignored += 1;
+        return;
+    };
+
+    // location as 'a..b', 'complement(a..b)' or 'join(a..b,c..d)', possibly with partial ends ('<', '>')
+    let mut segments: Vec<(u32, u32)> = Vec::new();
+    let cleaned: String = location
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+        .collect();
+    for range in cleaned.split(',') {
+        let bounds: Vec<u32> = range
+            .split("..")
+            .filter_map(|bound| bound.parse().ok())
+            .collect();
+        match bounds.as_slice() {
+            [start, end] => segments.push((*start, *end)),
+            [position] => segments.push((*position, *position)),
+            _ => {}
+        }
+    }
+    if segments.is_empty() {
+        return;
+    }
+    segments.sort_unstable();
+
+    let qualifier = |name: &str| {
+        qualifiers
+            .iter()
+            .find_map(|qualifier| qualifier.strip_prefix(&format!("/{}=", name)))
+            .map(|value| value.trim_matches('"').to_string())
+    };
+    let locus_tag = qualifier("locus_tag").unwrap_or_default();
+    let name = qualifier("gene").unwrap_or(locus_tag.clone());
+    // first base of the first complete codon (1-3)
+    let phase = qualifier("codon_start")
+        .and_then(|value| value.parse::<u32>().ok())
+        .map_or(0, |codon_start| codon_start.saturating_sub(1) % 3);
+
+    genes.push(Gene {
+        contig,
+        segments,
+        forward: !location.contains("complement"),
+        phase,
+        name,
+        locus_tag,
+    });
+}
+
+// INFO annotation of a variant (0-based position of the REF allele) located in one or more CDS: gene name, locus
+// tag, codon position and, for each ALT allele, effect and amino acid change (None if intergenic); values of
+// overlapping CDS are comma-separated, and EFF and AA list the ALT alleles of each CDS in turn
+pub fn annotate_variant(
+    genes: &GeneIndex,
+    contigs: &[Contig],
+    contig: usize,
+    position: u32,
+    ref_allele: &str,
+    alt_alleles: &[String],
+) -> Option<String> {
+    // first variable base (after the anchoring base of indels)
+    let is_indel = alt_alleles.iter().any(|alt| alt.len() != ref_allele.len());
+    let first_base = position + 1 + is_indel as u32;
+
+    let mut names: Vec<&str> = Vec::new();
+    let mut locus_tags: Vec<&str> = Vec::new();
+    let mut codon_positions: Vec<String> = Vec::new();
+    let mut effects: Vec<String> = Vec::new();
+    let mut aa_changes: Vec<String> = Vec::new();
+    for gene in genes.overlapping(contig, first_base) {
+        let Some(cds_offset) = gene.cds_offset(first_base) else {
+            continue;
+        };
+        names.push(if gene.name.is_empty() {
+            "."
+        } else {
+            &gene.name
+        });
+        locus_tags.push(if gene.locus_tag.is_empty() {
+            "."
+        } else {
+            &gene.locus_tag
+        });
+        codon_positions.push((cds_offset % 3 + 1).to_string());
+        for alt_allele in alt_alleles {
+            if is_indel {
+                let length_difference =
+                    (ref_allele.len() as i64 - alt_allele.len() as i64).unsigned_abs();
+                effects.push(
+                    if length_difference.is_multiple_of(3) {
+                        "inframe_indel"
+                    } else {
+                        "frameshift"
+                    }
+                    .to_string(),
+                );
+                aa_changes.push(".".to_string());
+            } else {
+                let (effect, aa_change) =
+                    substitution_effect(gene, &contigs[contig].seq, position + 1, alt_allele);
+                effects.push(effect);
+                aa_changes.push(aa_change);
+            }
+        }
+    }
+    if names.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        ";GENE={};LTAG={};CPOS={};EFF={};AA={}",
+        names.join(","),
+        locus_tags.join(","),
+        codon_positions.join(","),
+        effects.join(","),
+        aa_changes.join(",")
+    ))
+}
+
+// effect and amino acid change (e.g., 'K23N') of a substitution starting at a 1-based genomic position
+fn substitution_effect(
+    gene: &Gene,
+    contig_seq: &[u8],
+    position: u32,
+    alt_allele: &str,
+) -> (String, String) {
+    let unknown = ("unknown".to_string(), ".".to_string());
+
+    // CDS offsets of the substituted bases and their new nucleotide (in the orientation of the gene)
+    let mut new_bases: HashMap<u32, u8> = HashMap::new();
+    for (i, base) in alt_allele.bytes().enumerate() {
+        if let Some(offset) = gene.cds_offset(position + i as u32) {
+            new_bases.insert(offset, if gene.forward { base } else { complement(base) });
+        }
+    }
+    let (Some(&first_offset), Some(&last_offset)) =
+        (new_bases.keys().min(), new_bases.keys().max())
+    else {
+        return unknown;
+    };
+
+    // reference and alternative codons overlapping the substitution
+    let (first_codon, last_codon) = (first_offset / 3, last_offset / 3);
+    if (last_codon + 1) * 3 > gene.cds_length() {
+        return unknown;
+    }
+    let mut ref_aa = String::new();
+    let mut alt_aa = String::new();
+    for codon in first_codon..=last_codon {
+        let mut ref_codon = [0u8; 3];
+        let mut alt_codon = [0u8; 3];
+        for i in 0..3 {
+            let offset = codon * 3 + i;
+            let Some(genomic_position) = gene.genomic_position(offset) else {
+                return unknown;
+            };
+            let Some(&base) = contig_seq.get(genomic_position as usize - 1) else {
+                return unknown;
+            };
+            ref_codon[i as usize] = if gene.forward { base } else { complement(base) };
+            alt_codon[i as usize] = *new_bases.get(&offset).unwrap_or(&ref_codon[i as usize]);
+        }
+        ref_aa.push(translate_codon(&ref_codon));
+        alt_aa.push(translate_codon(&alt_codon));
+    }
+
+    let effect = if ref_aa == alt_aa {
+        "synonymous"
+    } else if alt_aa.contains('*') && !ref_aa.contains('*') {
+        "stop_gained"
+    } else if ref_aa.contains('*') && !alt_aa.contains('*') {
+        "stop_lost"
+    } else {
+        "non_synonymous"
+    };
+    (
+        effect.to_string(),
+        format!("{}{}{}", ref_aa, first_codon + 1, alt_aa),
+    )
+}
+
+fn complement(base: u8) -> u8 {
+    match base {
+        b'A' => b'T',
+        b'T' => b'A',
+        b'C' => b'G',
+        b'G' => b'C',
+        _ => b'N',
+    }
+}
+
+// translate a codon with the bacterial genetic code (table 11), 'X' if ambiguous
+fn translate_codon(codon: &[u8; 3]) -> char {
+    const AMINO_ACIDS: &[u8; 64] =
+        b"KNKNTTTTRSRSIIMIQHQHPPPPRRRRLLLLEDEDAAAAGGGGVVVV*Y*YSSSS*CWCLFLF";
+    let mut index = 0;
+    for &base in codon {
+        let value = match base {
+            b'A' => 0,
+            b'C' => 1,
+            b'G' => 2,
+            b'T' => 3,
+            _ => return 'X',
+        };
+        index = index * 4 + value;
+    }
+    AMINO_ACIDS[index] as char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|line| line.to_string()).collect()
+    }
+
+    // genbank line with the feature key (or nothing) in the first 21 columns
+    fn genbank_line(key: &str, value: &str) -> String {
+        format!("     {:<16}{}", key, value)
+    }
+
+    fn gene(segments: Vec<(u32, u32)>, forward: bool, phase: u32) -> Gene {
+        named_gene(segments, forward, phase, "1")
+    }
+
+    fn named_gene(segments: Vec<(u32, u32)>, forward: bool, phase: u32, id: &str) -> Gene {
+        Gene {
+            contig: 0,
+            segments,
+            forward,
+            phase,
+            name: format!("g{}", id),
+            locus_tag: format!("L{}", id),
+        }
+    }
+
+    #[test]
+    fn translate_codons() {
+        assert_eq!(translate_codon(b"ATG"), 'M');
+        assert_eq!(translate_codon(b"AAA"), 'K');
+        assert_eq!(translate_codon(b"GCT"), 'A');
+        assert_eq!(translate_codon(b"TGG"), 'W');
+        assert_eq!(translate_codon(b"TAA"), '*');
+        assert_eq!(translate_codon(b"TGA"), '*');
+        assert_eq!(translate_codon(b"ANA"), 'X');
+    }
+
+    #[test]
+    fn cds_offsets_with_phase() {
+        let cds = gene(vec![(2, 14)], true, 1);
+        assert_eq!(cds.cds_offset(2), None);
+        assert_eq!(cds.cds_offset(3), Some(0));
+        assert_eq!(cds.genomic_position(0), Some(3));
+        assert_eq!(cds.cds_length(), 12);
+
+        let cds = gene(vec![(3, 8), (11, 16)], false, 0);
+        assert_eq!(cds.cds_offset(16), Some(0));
+        assert_eq!(cds.cds_offset(8), Some(6));
+        assert_eq!(cds.cds_offset(10), None);
+        assert_eq!(cds.genomic_position(7), Some(7));
+    }
+
+    #[test]
+    fn annotate_substitutions_and_indels() {
+        // ATG AAA TTT TAA at positions 3-14
+        let contigs = vec![Contig::new(
+            "ctg1".to_string(),
+            b"CCATGAAATTTTAAGG".to_vec(),
+        )];
+        let genes = GeneIndex::new(vec![gene(vec![(3, 14)], true, 0)], 1);
+        let alt = |allele: &str| vec![allele.to_string()];
+
+        assert_eq!(
+            annotate_variant(&genes, &contigs, 0, 7, "A", &alt("C")).unwrap(),
+            ";GENE=g1;LTAG=L1;CPOS=3;EFF=non_synonymous;AA=K2N"
+        );
+        assert_eq!(
+            annotate_variant(&genes, &contigs, 0, 7, "A", &alt("G")).unwrap(),
+            ";GENE=g1;LTAG=L1;CPOS=3;EFF=synonymous;AA=K2K"
+        );
+        assert_eq!(
+            annotate_variant(&genes, &contigs, 0, 5, "A", &alt("T")).unwrap(),
+            ";GENE=g1;LTAG=L1;CPOS=1;EFF=stop_gained;AA=K2*"
+        );
+        assert_eq!(
+            annotate_variant(&genes, &contigs, 0, 12, "A", &alt("C")).unwrap(),
+            ";GENE=g1;LTAG=L1;CPOS=2;EFF=stop_lost;AA=*4S"
+        );
+        assert_eq!(
+            annotate_variant(&genes, &contigs, 0, 7, "A", &alt("AT")).unwrap(),
+            ";GENE=g1;LTAG=L1;CPOS=1;EFF=frameshift;AA=."
+        );
+        assert_eq!(
+            annotate_variant(&genes, &contigs, 0, 7, "A", &alt("ATTT")).unwrap(),
+            ";GENE=g1;LTAG=L1;CPOS=1;EFF=inframe_indel;AA=."
+        );
+        assert_eq!(
+            annotate_variant(&genes, &contigs, 0, 0, "C", &alt("T")),
+            None
+        );
+
+        // same CDS on the reverse strand
+        let contigs = vec![Contig::new(
+            "ctg1".to_string(),
+            b"CCTTAAAATTTCATGG".to_vec(),
+        )];
+        let genes = GeneIndex::new(vec![gene(vec![(3, 14)], false, 0)], 1);
+        assert_eq!(
+            annotate_variant(&genes, &contigs, 0, 8, "T", &alt("G")).unwrap(),
+            ";GENE=g1;LTAG=L1;CPOS=3;EFF=non_synonymous;AA=K2N"
+        );
+
+        // CDS starting one base before its first complete codon
+        let contigs = vec![Contig::new(
+            "ctg1".to_string(),
+            b"CCATGAAATTTTAAGG".to_vec(),
+        )];
+        let genes = GeneIndex::new(vec![gene(vec![(2, 14)], true, 1)], 1);
+        assert_eq!(
+            annotate_variant(&genes, &contigs, 0, 7, "A", &alt("C")).unwrap(),
+            ";GENE=g1;LTAG=L1;CPOS=3;EFF=non_synonymous;AA=K2N"
+        );
+        assert_eq!(
+            annotate_variant(&genes, &contigs, 0, 1, "C", &alt("T")),
+            None
+        );
+    }
+
+    #[test]
+    fn annotate_overlapping_genes() {
+        // ATG AAA TTT TAA at positions 3-14, and AAA TTT TAA at positions 6-14 (overlapping CDS)
+        let contigs = vec![Contig::new(
+            "ctg1".to_string(),
+            b"CCATGAAATTTTAAGG".to_vec(),
+        )];
+        let genes = GeneIndex::new(
+            vec![
+                named_gene(vec![(6, 14)], true, 0, "2"),
+                named_gene(vec![(3, 14)], true, 0, "1"),
+            ],
+            1,
+        );
+        let alt = |alleles: &[&str]| {
+            alleles
+                .iter()
+                .map(|allele| allele.to_string())
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(
+            annotate_variant(&genes, &contigs, 0, 7, "A", &alt(&["C", "G"])).unwrap(),
+            ";GENE=g1,g2;LTAG=L1,L2;CPOS=3,3;EFF=non_synonymous,synonymous,non_synonymous,synonymous;AA=K2N,K2K,K1N,K1K"
+        );
+        // only in the first CDS
+        assert_eq!(
+            annotate_variant(&genes, &contigs, 0, 3, "T", &alt(&["C"])).unwrap(),
+            ";GENE=g1;LTAG=L1;CPOS=2;EFF=non_synonymous;AA=M1T"
+        );
+    }
+
+    #[test]
+    fn gene_index_overlaps() {
+        let genes = GeneIndex::new(
+            vec![
+                named_gene(vec![(50, 60)], true, 0, "3"),
+                named_gene(vec![(1, 100)], true, 0, "1"),
+                named_gene(vec![(10, 20)], false, 0, "2"),
+            ],
+            2,
+        );
+        let names = |position: u32| {
+            genes
+                .overlapping(0, position)
+                .map(|gene| gene.name.clone())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(genes.len(), 3);
+        assert_eq!(names(15), vec!["g1", "g2"]);
+        assert_eq!(names(30), vec!["g1"]);
+        assert_eq!(names(60), vec!["g1", "g3"]);
+        assert!(names(101).is_empty());
+        assert_eq!(genes.overlapping(1, 15).count(), 0);
+        assert_eq!(genes.overlapping(2, 15).count(), 0);
+    }
+
+    #[test]
+    fn parse_gff3_cds() {
+        let contig_indexes: HashMap<&str, usize> = [("ctg1", 0)].into_iter().collect();
+        let gff = lines(&[
+            "##gff-version 3",
+            "ctg1\t.\tgene\t3\t20\t.\t+\t.\tID=gene1",
+            "ctg1\t.\tCDS\t3\t8\t.\t+\t0\tID=cds1;gene=abc;locus_tag=L1",
+            "ctg1\t.\tCDS\t10\t20\t.\t+\t0\tID=cds1;gene=abc;locus_tag=L1",
+            "other\t.\tCDS\t1\t9\t.\t+\t0\tID=cds2",
+            "ctg1\t.\tCDS\t30\t41\t.\t-\t2\tID=cds3;Name=xyz",
+            "ctg1\t.\tCDS\t50\t60\t.\t-\t1\tID=cds4;locus_tag=L4",
+            "ctg1\t.\tCDS\t70\t80\t.\t-\t0\tID=cds4;locus_tag=L4",
+            "##FASTA",
+            "ctg1\t.\tCDS\t90\t99\t.\t+\t0\tID=cds5",
+        ]);
+        let (genes, nb_ignored) = parse_gff3(&gff, &contig_indexes);
+
+        assert_eq!(nb_ignored, 1);
+        assert_eq!(genes.len(), 3);
+        assert_eq!(genes[0].segments, vec![(3, 8), (10, 20)]);
+        assert!(genes[0].forward);
+        assert_eq!(
+            (genes[0].name.as_str(), genes[0].locus_tag.as_str()),
+            ("abc", "L1")
+        );
+        assert_eq!(
+            (genes[1].forward, genes[1].phase, genes[1].name.as_str()),
+            (false, 2, "xyz")
+        );
+        // phase of the first segment in the orientation of the gene
+        assert_eq!(genes[2].segments, vec![(50, 60), (70, 80)]);
+        assert_eq!((genes[2].phase, genes[2].name.as_str()), (0, "L4"));
+    }
+
+    #[test]
+    fn parse_genbank_cds() {
+        let contig_indexes: HashMap<&str, usize> = [("ctg1", 0)].into_iter().collect();
+        let genbank = vec![
+            "LOCUS       ctg1    100 bp    DNA     linear   BCT 01-JAN-2000".to_string(),
+            "FEATURES             Location/Qualifiers".to_string(),
+            genbank_line("gene", "3..14"),
+            genbank_line("CDS", "3..14"),
+            genbank_line("", "/gene=\"abc\""),
+            genbank_line("", "/locus_tag=\"L1\""),
+            genbank_line("CDS", "complement(join(20..25,"),
+            genbank_line("", "30..41))"),
+            genbank_line("", "/locus_tag=\"L2\""),
+            genbank_line("", "/codon_start=2"),
+            "ORIGIN".to_string(),
+            "//".to_string(),
+            "LOCUS       other    50 bp    DNA     linear   BCT 01-JAN-2000".to_string(),
+            "FEATURES             Location/Qualifiers".to_string(),
+            genbank_line("CDS", "1..9"),
+            "ORIGIN".to_string(),
+            "//".to_string(),
+        ];
+        let (genes, nb_ignored) = parse_genbank(&genbank, &contig_indexes);
+
+        assert_eq!(nb_ignored, 1);
+        assert_eq!(genes.len(), 2);
+        assert_eq!(
+            (genes[0].segments.clone(), genes[0].forward, genes[0].phase),
+            (vec![(3, 14)], true, 0)
+        );
+        assert_eq!(
+            (genes[0].name.as_str(), genes[0].locus_tag.as_str()),
+            ("abc", "L1")
+        );
+        assert_eq!(
+            (genes[1].segments.clone(), genes[1].forward, genes[1].phase),
+            (vec![(20, 25), (30, 41)], false, 1)
+        );
+        assert_eq!(genes[1].name, "L2");
+    }
+}
@@ -1,89 +1,85 @@
-use hashbrown::{HashMap, HashSet};
+use hashbrown::HashSet;
 //use std::time::Instant;
 
-use rayon::prelude::*;
 use dashmap::DashMap;
+use rayon::prelude::*;
 
+use crate::graph::KmerGraph;
+use crate::utils::KmerInt;
 //use crate::utils::DATA_INFO;
 
-
-pub fn compact_graph(all_kmers: &mut HashMap<u128, Vec<u128>>, start_kmers: &HashSet<u128>, end_kmers: &HashSet<u128>) -> DashMap<u128, Vec<u128>> {
-    
+pub fn compact_graph<IntT: KmerInt>(
+    all_kmers: &mut KmerGraph<IntT>,
+    start_kmers: &HashSet<IntT>,
+    end_kmers: &HashSet<IntT>,
+) -> DashMap<IntT, Vec<IntT>> {
     //let data_info = DATA_INFO.get().unwrap();
 
     //let start = Instant::now();
-        
-    let compacted: DashMap<u128, Vec<u128>> = DashMap::new();
-    
+
+    let compacted: DashMap<IntT, Vec<IntT>> = DashMap::new();
+
     // from start k-mers
     start_kmers.par_iter().for_each(|kmer| {
-        if let Some(starting_kmers) = all_kmers.get(kmer) {
-            for starting_kmer in starting_kmers.iter() {
-                let mut current_kmer = *starting_kmer;
-
-                let mut visited: HashSet<u128> = HashSet::new();
-                let mut vec_visited: Vec<u128> = Vec::new();
-
-                let mut walking_along_path = true;
-
-                while walking_along_path {
-                    if let Some(next_kmer_data) = all_kmers.get(&current_kmer) {
-                        if next_kmer_data.len() == 1 && !visited.contains(&next_kmer_data[0]) {
-                            current_kmer = next_kmer_data[0];
-                            vec_visited.push(current_kmer);
-                            visited.insert(current_kmer);
-
-                            if end_kmers.contains(&current_kmer) || start_kmers.contains(&current_kmer) {
-                                walking_along_path = false;
-                            }
-                        } else {
+        for starting_kmer in all_kmers.next_kmers(*kmer) {
+            let mut current_kmer = starting_kmer;
+
+            let mut visited: HashSet<IntT> = HashSet::new();
+            let mut vec_visited: Vec<IntT> = Vec::new();
+
+            let mut walking_along_path = true;
+
+            while walking_along_path {
+                match single_next_kmer(all_kmers, current_kmer) {
+                    Some(next_kmer) if !visited.contains(&next_kmer) => {
+                        current_kmer = next_kmer;
+                        vec_visited.push(current_kmer);
+                        visited.insert(current_kmer);
+
+                        if end_kmers.contains(&current_kmer) || start_kmers.contains(&current_kmer)
+                        {
                             walking_along_path = false;
                         }
-                    } else {
-                        walking_along_path = false;
                     }
-                }
-                if vec_visited.len() > 1 {
-                    compacted.insert(*starting_kmer, vec_visited);
+                    _ => walking_along_path = false,
                 }
             }
+            if vec_visited.len() > 1 {
+                compacted.insert(starting_kmer, vec_visited);
+            }
         }
     });
 
     // from end k-mers
     end_kmers.par_iter().for_each(|kmer| {
-        if let Some(starting_kmers) = all_kmers.get(kmer) {
-            for starting_kmer in starting_kmers.iter() {
-                let mut current_kmer = *starting_kmer;
-
-                let mut visited: HashSet<u128> = HashSet::new();
-                let mut vec_visited: Vec<u128> = Vec::new();
-
-                let mut walking_along_path = true;
-
-                while walking_along_path {
-                    if let Some(next_kmer_data) = all_kmers.get(&current_kmer) {
-                        if next_kmer_data.len() == 1 && !visited.contains(&next_kmer_data[0]) {
-                            current_kmer = next_kmer_data[0];
-                            vec_visited.push(current_kmer);
-                            visited.insert(current_kmer);
-
-                            if end_kmers.contains(&current_kmer) || start_kmers.contains(&current_kmer) {
-                                walking_along_path = false;
-                            }
-                        } else {
+        for starting_kmer in all_kmers.next_kmers(*kmer) {
+            let mut current_kmer = starting_kmer;
+
+            let mut visited: HashSet<IntT> = HashSet::new();
+            let mut vec_visited: Vec<IntT> = Vec::new();
+
+            let mut walking_along_path = true;
+
+            while walking_along_path {
+                match single_next_kmer(all_kmers, current_kmer) {
+                    Some(next_kmer) if !visited.contains(&next_kmer) => {
+                        current_kmer = next_kmer;
+                        vec_visited.push(current_kmer);
+                        visited.insert(current_kmer);
+
+                        if end_kmers.contains(&current_kmer) || start_kmers.contains(&current_kmer)
+                        {
                             walking_along_path = false;
                         }
-                    } else {
-                        walking_along_path = false;
                     }
-                }
-                // could be "1" but for some reason I get more variant groups with k_graph
-                //if vec_visited.len() > data_info.k_graph {
-                if vec_visited.len() > 1 {
-                    compacted.insert(*starting_kmer, vec_visited);
+                    _ => walking_along_path = false,
                 }
             }
+            // could be "1" but for some reason I get more variant groups with k_graph
+            //if vec_visited.len() > data_info.k_graph {
+            if vec_visited.len() > 1 {
+                compacted.insert(starting_kmer, vec_visited);
+            }
         }
     });
 
@@ -91,30 +87,39 @@ pub fn compact_graph(all_kmers: &mut HashMap<u128, Vec<u128>>, start_kmers: &Has
     //println!("time taken: {:?}", duration);
 
     //let start = Instant::now();
-    
-    // modify graph and compacted vector
+
+    // modify graph and compacted vector (in k-mer order to get the same graph between runs)
     //let mut nb_removed = 0;
-    for mut item in compacted.iter_mut() {
-        let (starting_kmer, vec_visited) = item.pair_mut();
+    let mut sorted_starting_kmers: Vec<IntT> = compacted.iter().map(|item| *item.key()).collect();
+    sorted_starting_kmers.sort_unstable();
+    for starting_kmer in &sorted_starting_kmers {
+        let mut vec_visited = compacted.get_mut(starting_kmer).unwrap();
         // remove edges corresponding to compacted vector
-        all_kmers.get_mut(starting_kmer).unwrap().retain(|&neighbor| neighbor != vec_visited[0]);
-        for window in vec_visited[..vec_visited.len() -1].windows(2) {
-            all_kmers.get_mut(&window[0]).unwrap().retain(|&neighbor| neighbor != window[1]);
+        all_kmers.remove_edge(*starting_kmer, vec_visited[0]);
+        for window in vec_visited[..vec_visited.len() - 1].windows(2) {
+            all_kmers.remove_edge(window[0], window[1]);
             //nb_removed += 1
-        }        
-        
+        }
+
         // add new edge in place of compacted segment
-        all_kmers.entry(*starting_kmer)
-                 .or_default()
-                 .push(vec_visited[vec_visited.len() -1]);
+        all_kmers.add_shortcut(*starting_kmer, vec_visited[vec_visited.len() - 1]);
 
         // remove last element of compact vector
         vec_visited.pop();
     }
-        
+
     //let duration = start.elapsed();
-    //println!("{} edges removed", nb_removed);    
-    //println!("time taken: {:?}", duration);    
+    //println!("{} edges removed", nb_removed);
+    //println!("time taken: {:?}", duration);
 
     compacted
 }
+
+// next node of a node with a single next node
+fn single_next_kmer<IntT: KmerInt>(all_kmers: &KmerGraph<IntT>, kmer: IntT) -> Option<IntT> {
+    let mut next_kmers = all_kmers.next_kmers(kmer);
+    match (next_kmers.next(), next_kmers.next()) {
+        (Some(next_kmer), None) => Some(next_kmer),
+        _ => None,
+    }
+}
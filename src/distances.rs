@@ -0,0 +1,195 @@
+use hashbrown::HashMap;
+use std::io::{self, Write};
+
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+
+use crate::error::SkaloError;
+use crate::utils::SnpInfo;
+
+// pairwise SNP distances between samples (same order as the sample names)
+pub type DistanceMatrix = Vec<Vec<usize>>;
+
+// single-linkage clusters of samples (cluster id of each sample) at a SNP threshold
+pub type SampleClusters = (usize, Vec<usize>);
+
+// compute the number of SNPs between each pair of samples; positions with missing or ambiguous data ('-', 'N' or
+// IUPAC code of a mixed call) in one of the 2 samples are ignored, or counted as a difference if 'count_missing'
+pub fn snp_distances(
+    snps: &HashMap<(usize, u32), SnpInfo>,
+    nb_samples: usize,
+    count_missing: bool,
+    nb_threads: usize,
+) -> Result<DistanceMatrix, SkaloError> {
+    // SNP sequence of each sample
+    let mut sequences: Vec<Vec<u8>> = vec![Vec::with_capacity(snps.len()); nb_samples];
+    for snp in snps.values() {
+        for (sequence, &nucl) in sequences.iter_mut().zip(&snp.column) {
+            sequence.push(nucl as u8);
+        }
+    }
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(nb_threads)
+        .build()
+        .map_err(|e| SkaloError::ThreadPool(e.to_string()))?;
+
+    // distances to the previous samples, then mirrored
+    let lower_rows: Vec<Vec<usize>> = pool.install(|| {
+        (0..nb_samples)
+            .into_par_iter()
+            .map(|i| {
+                (0..i)
+                    .map(|j| pairwise_distance(&sequences[i], &sequences[j], count_missing))
+                    .collect()
+            })
+            .collect()
+    });
+
+    let mut distances: DistanceMatrix = vec![vec![0; nb_samples]; nb_samples];
+    for (i, row) in lower_rows.iter().enumerate() {
+        for (j, &distance) in row.iter().enumerate() {
+            distances[i][j] = distance;
+            distances[j][i] = distance;
+        }
+    }
+    Ok(distances)
+}
+
+fn pairwise_distance(sequence_1: &[u8], sequence_2: &[u8], count_missing: bool) -> usize {
+    let is_missing = |nucl: u8| !matches!(nucl, b'A' | b'C' | b'G' | b'T');
+    sequence_1
+        .iter()
+        .zip(sequence_2)
+        .filter(
+            |&(&nucl_1, &nucl_2)| match (is_missing(nucl_1), is_missing(nucl_2)) {
+                (false, false) => nucl_1 != nucl_2,
+                (true, true) => false,
+                _ => count_missing,
+            },
+        )
+        .count()
+}
+
+// single-linkage clustering: samples at a distance <= threshold are in the same cluster
+// (cluster ids are numbered from 1 in the order of the samples)
+pub fn single_linkage_clusters(distances: &DistanceMatrix, threshold: usize) -> Vec<usize> {
+    let nb_samples = distances.len();
+
+    // union-find of the samples
+    let mut parents: Vec<usize> = (0..nb_samples).collect();
+    for (i, row) in distances.iter().enumerate() {
+        for (j, &distance) in row.iter().enumerate().take(i) {
+            if distance <= threshold {
+                let (root_i, root_j) = (find_root(&mut parents, i), find_root(&mut parents, j));
+                parents[root_i.max(root_j)] = root_i.min(root_j);
+            }
+        }
+    }
+
+    let mut cluster_ids: HashMap<usize, usize> = HashMap::new();
+    (0..nb_samples)
+        .map(|sample| {
+            let root = find_root(&mut parents, sample);
+            let nb_clusters = cluster_ids.len();
+            *cluster_ids.entry(root).or_insert(nb_clusters + 1)
+        })
+        .collect()
+}
+
+// root of a sample in the union-find (with path halving)
+fn find_root(parents: &mut [usize], mut sample: usize) -> usize {
+    while parents[sample] != sample {
+        parents[sample] = parents[parents[sample]];
+        sample = parents[sample];
+    }
+    sample
+}
+
+// write the cluster id of each sample at each threshold in TSV format
+pub fn write_clusters(
+    output: &mut impl Write,
+    clusters: &[SampleClusters],
+    sample_names: &[String],
+) -> io::Result<()> {
+    let thresholds: Vec<String> = clusters
+        .iter()
+        .map(|(threshold, _)| format!("cluster_{}", threshold))
+        .collect();
+    writeln!(output, "sample\t{}", thresholds.join("\t"))?;
+    for (i, name) in sample_names.iter().enumerate() {
+        let ids: Vec<String> = clusters
+            .iter()
+            .map(|(_, cluster_ids)| cluster_ids[i].to_string())
+            .collect();
+        writeln!(output, "{}\t{}", name, ids.join("\t"))?;
+    }
+    Ok(())
+}
+
+// write the distance matrix in TSV format (one row and one column per sample)
+pub fn write_distance_matrix(
+    output: &mut impl Write,
+    distances: &DistanceMatrix,
+    sample_names: &[String],
+) -> io::Result<()> {
+    writeln!(output, "sample\t{}", sample_names.join("\t"))?;
+    for (name, row) in sample_names.iter().zip(distances) {
+        let row: Vec<String> = row.iter().map(|distance| distance.to_string()).collect();
+        writeln!(output, "{}\t{}", name, row.join("\t"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::VariantAnnotation;
+
+    #[test]
+    fn pairwise_distance_missing_data() {
+        // missing data and mixed calls in one sample are only counted with 'count_missing'
+        assert_eq!(pairwise_distance(b"ACGTAN", b"ACTT-R", false), 1);
+        assert_eq!(pairwise_distance(b"ACGTAN", b"ACTT-R", true), 2);
+        assert_eq!(pairwise_distance(b"ACGTR", b"ACGTA", true), 1);
+        assert_eq!(pairwise_distance(b"ACGT", b"ACGT", true), 0);
+    }
+
+    #[test]
+    fn snp_distance_matrix() {
+        let snps: HashMap<(usize, u32), SnpInfo> = ["AAC", "CA-", "GGG"]
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                (
+                    (0, i as u32),
+                    SnpInfo {
+                        column: column.chars().collect(),
+                        annotation: VariantAnnotation::default(),
+                    },
+                )
+            })
+            .collect();
+        let distances = snp_distances(&snps, 3, false, 2).unwrap();
+        assert_eq!(distances, vec![vec![0, 1, 1], vec![1, 0, 1], vec![1, 1, 0]]);
+        let distances = snp_distances(&snps, 3, true, 1).unwrap();
+        assert_eq!(distances, vec![vec![0, 1, 2], vec![1, 0, 2], vec![2, 2, 0]]);
+    }
+
+    #[test]
+    fn single_linkage_thresholds() {
+        // 0-1 and 1-2 are close (chaining), 3-4 are close
+        let distances: DistanceMatrix = vec![
+            vec![0, 2, 10, 10, 10],
+            vec![2, 0, 3, 10, 10],
+            vec![10, 3, 0, 10, 10],
+            vec![10, 10, 10, 0, 1],
+            vec![10, 10, 10, 1, 0],
+        ];
+        assert_eq!(single_linkage_clusters(&distances, 0), vec![1, 2, 3, 4, 5]);
+        assert_eq!(single_linkage_clusters(&distances, 2), vec![1, 1, 2, 3, 3]);
+        assert_eq!(single_linkage_clusters(&distances, 3), vec![1, 1, 1, 2, 2]);
+        assert_eq!(single_linkage_clusters(&distances, 10), vec![1, 1, 1, 1, 1]);
+        assert!(single_linkage_clusters(&Vec::new(), 1).is_empty());
+    }
+}
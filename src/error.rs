@@ -0,0 +1,65 @@
+use std::fmt;
+
+// errors returned by the skalo pipeline, grouped by category (each with its own exit code)
+#[derive(Debug)]
+pub enum SkaloError {
+    // the skf file could not be read or is corrupted
+    InputFile(String),
+    // invalid nucleotide found while encoding a k-mer
+    InvalidNucleotide(char),
+    // the reference genome could not be read or is invalid
+    Reference(String),
+    // the dataset does not contain any variant
+    NoVariant(String),
+    // an output file could not be written
+    Output(String),
+    // the annotation file of the reference genome could not be read or is invalid
+    Annotation(String),
+    // the FASTA/FASTQ files of the samples could not be read
+    SequenceFile(String),
+    // the samples to include or exclude, or the sample sheet, could not be read or are invalid
+    Samples(String),
+    // the pool of threads could not be created (e.g., invalid number of threads)
+    ThreadPool(String),
+}
+
+impl SkaloError {
+    // exit code of the program for each category of error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SkaloError::InputFile(_) => 3,
+            SkaloError::InvalidNucleotide(_) => 4,
+            SkaloError::Reference(_) => 5,
+            SkaloError::NoVariant(_) => 6,
+            SkaloError::Output(_) => 7,
+            SkaloError::Annotation(_) => 8,
+            SkaloError::SequenceFile(_) => 9,
+            SkaloError::Samples(_) => 10,
+            SkaloError::ThreadPool(_) => 11,
+        }
+    }
+}
+
+impl fmt::Display for SkaloError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SkaloError::InputFile(msg) => write!(f, "could not read the skf file ({})", msg),
+            SkaloError::InvalidNucleotide(nucl) => write!(f, "invalid nucleotide '{}'", nucl),
+            SkaloError::Reference(msg) => write!(f, "invalid reference genome ({})", msg),
+            SkaloError::NoVariant(msg) => write!(f, "{}", msg),
+            SkaloError::Output(msg) => write!(f, "could not write output ({})", msg),
+            SkaloError::Annotation(msg) => write!(f, "invalid annotation file ({})", msg),
+            SkaloError::SequenceFile(msg) => {
+                write!(f, "could not read the sequence files ({})", msg)
+            }
+            SkaloError::Samples(msg) => {
+                write!(f, "invalid sample selection or sample sheet ({})", msg)
+            }
+            SkaloError::ThreadPool(msg) => {
+                write!(f, "could not create the pool of threads ({})", msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SkaloError {}
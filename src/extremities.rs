@@ -1,37 +1,40 @@
-use hashbrown::{HashMap, HashSet};
+use hashbrown::HashSet;
 //use std::time::Instant;
-use bit_set::BitSet;
 
-use crate::utils::{rev_compl_u128, DATA_INFO};
+use crate::error::SkaloError;
+use crate::graph::{KmerGraph, KmerSamples};
+use crate::utils::{progress, rev_compl_kmer, Config, DataInfo, KmerInt};
 
+pub fn identify_good_kmers<IntT: KmerInt>(
+    all_kmers: &KmerGraph<IntT>,
+    kmer_2_samples: &KmerSamples<IntT>,
+    arguments: &Config,
+    data_info: &DataInfo,
+) -> Result<(HashSet<IntT>, HashSet<IntT>), SkaloError> {
+    progress!(arguments.verbose, " # identify bubble extremities");
 
-pub fn identify_good_kmers(all_kmers: &HashMap<u128, Vec<u128>>, kmer_2_samples: &HashMap<u128, BitSet>) -> (HashSet<u128>, HashSet<u128>) {
-    
-    println!(" # identify bubble extremities");
-    
-    let data_info = DATA_INFO.get().unwrap();
-
-    let mut start_kmers: HashSet<u128> = HashSet::new();
-    let mut end_kmers: HashSet<u128> = HashSet::new();
+    let mut start_kmers: HashSet<IntT> = HashSet::new();
+    let mut end_kmers: HashSet<IntT> = HashSet::new();
 
     //let start = Instant::now();
 
     // iterate over all_kmers
-    for (kmer, next_kmers) in all_kmers.iter() {
-        if next_kmers.len() > 1 {
+    for kmer in all_kmers.nodes() {
+        if all_kmers.nb_next_kmers(kmer) > 1 {
+            let next_kmers: Vec<IntT> = all_kmers.next_kmers(kmer).collect();
             'i_loop: for (i, &kmer1) in next_kmers.iter().enumerate() {
                 for &kmer2 in next_kmers.iter().skip(i + 1) {
-                    
-                    let full_kmer1 = combine_kmers(*kmer, kmer1);
-                    let full_kmer2 = combine_kmers(*kmer, kmer2);
-                    
-                    let samples1 = kmer_2_samples.get(&full_kmer1).unwrap();
-                    let samples2 = kmer_2_samples.get(&full_kmer2).unwrap();
-                    
-                    if compare_samples(samples1, samples2) {
-                        start_kmers.insert(*kmer);
-                        end_kmers.insert(rev_compl_u128(*kmer, data_info.k_graph));
-                                                               
+                    let full_kmer1 = combine_kmers(kmer, kmer1);
+                    let full_kmer2 = combine_kmers(kmer, kmer2);
+
+                    // k-mers with different samples have different colours
+                    let colour1 = kmer_2_samples.colour(&full_kmer1).unwrap();
+                    let colour2 = kmer_2_samples.colour(&full_kmer2).unwrap();
+
+                    if colour1 != colour2 {
+                        start_kmers.insert(kmer);
+                        end_kmers.insert(rev_compl_kmer(kmer, data_info.k_graph));
+
                         //uncomment to print network
                         /*
                         use crate::utils::{rev_compl, decode_kmer};
@@ -39,51 +42,42 @@ pub fn identify_good_kmers(all_kmers: &HashMap<u128, Vec<u128>>, kmer_2_samples:
                         let rc = rev_compl(&dna);
 
                         println!("{}	{}	red	", &kmer, &kmer);
-                        println!("{}	{}	red	", &rev_compl_u128(*kmer, data_info.k_graph), &rev_compl_u128(*kmer, data_info.k_graph));
+                        println!("{}	{}	red	", &rev_compl_kmer(*kmer, data_info.k_graph), &rev_compl_kmer(*kmer, data_info.k_graph));
                         */
-                        
+
                         break 'i_loop;
                     }
                 }
             }
         }
     }
-    
-    // exit program if no extremity found (eg, cases of weeded skf files)
+
+    // stop if no extremity found (eg, cases of weeded skf files)
     if start_kmers.is_empty() {
-        eprintln!("\n      Error: there is no entry node in this graph, hence no variant.\n");
-        std::process::exit(1);
+        return Err(SkaloError::NoVariant(
+            "there is no entry node in this graph, hence no variant".to_string(),
+        ));
     }
 
-    println!("     . {} entry nodes", start_kmers.len());
+    progress!(
+        arguments.verbose,
+        "     . {} entry nodes",
+        start_kmers.len()
+    );
 
     //let duration = start.elapsed();
     //println!("time taken: {:?}", duration);
 
-    (start_kmers, end_kmers)
+    Ok((start_kmers, end_kmers))
 }
 
-
-pub fn combine_kmers(encoded_kmer1: u128, encoded_kmer2: u128) -> u128 {
-    // define the bit mask for extracting the last nucleotide of the second k-mer
-    let last_nucleotide_mask: u128 = 0b11; // Mask for 2 bits
-
+pub fn combine_kmers<IntT: KmerInt>(encoded_kmer1: IntT, encoded_kmer2: IntT) -> IntT {
     // shift the first k-mer left by 2 bits to make space for the new nucleotide
     let shifted_kmer1 = encoded_kmer1 << 2;
 
     // extract the last nucleotide from the second k-mer
-    let last_nucleotide = encoded_kmer2 & last_nucleotide_mask;
+    let last_nucleotide = IntT::from_encoded_base(encoded_kmer2.lsb_u8() & 0b11);
 
     // combine the two k-mers into a (k+1)-mer encoding
     shifted_kmer1 | last_nucleotide
 }
-
-
-fn compare_samples(bitset_1: &BitSet, bitset_2: &BitSet) -> bool {
-    let diff_count = bitset_1.symmetric_difference(bitset_2).count();
-    diff_count != 0
-}
-
-
-
-
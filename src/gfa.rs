@@ -0,0 +1,303 @@
+use bit_set::BitSet;
+use hashbrown::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use crate::error::SkaloError;
+use crate::extremities::combine_kmers;
+use crate::graph::{canonical_kmer, KmerGraph, KmerSamples};
+use crate::output::write_file;
+use crate::utils::{
+    decode_kmer, encode_u8_kmer, get_last_nucl, progress, rev_compl_kmer, Config, DataInfo,
+    KmerInt, VariantGroups,
+};
+
+// export the colored de Bruijn graph as compacted unitigs (segments) and their edges (links) in GFA format
+pub fn export_graph_gfa<IntT: KmerInt>(
+    all_kmers: &KmerGraph<IntT>,
+    kmer_2_samples: &KmerSamples<IntT>,
+    data_info: &DataInfo,
+    arguments: &Config,
+) -> Result<(), SkaloError> {
+    let k_graph = data_info.k_graph;
+
+    // collect all nodes of the graph (sorted to get the same unitigs between runs)
+    let mut sorted_nodes: Vec<IntT> = all_kmers.nodes().collect();
+    sorted_nodes.sort_unstable();
+
+    // build unitigs: maximal paths of nodes with a single successor and a single predecessor
+    let mut unitigs: Vec<Vec<IntT>> = Vec::new();
+    let mut visited: HashSet<IntT> = HashSet::new();
+
+    for &node in &sorted_nodes {
+        if visited.contains(&canonical_kmer(node, k_graph)) {
+            continue;
+        }
+
+        // walk backward to the first node of the unitig
+        let mut first = node;
+        let mut seen: HashSet<IntT> = HashSet::from([canonical_kmer(node, k_graph)]);
+        loop {
+            let previous = predecessors(all_kmers, first, k_graph);
+            if previous.len() != 1 || successors(all_kmers, previous[0]).len() != 1 {
+                break;
+            }
+            // stop on cycles and hairpins
+            if !seen.insert(canonical_kmer(previous[0], k_graph))
+                || visited.contains(&canonical_kmer(previous[0], k_graph))
+            {
+                break;
+            }
+            first = previous[0];
+        }
+
+        // walk forward to the last node of the unitig
+        let mut unitig = vec![first];
+        visited.insert(canonical_kmer(first, k_graph));
+        let mut current = first;
+        loop {
+            let next = successors(all_kmers, current);
+            if next.len() != 1 || predecessors(all_kmers, next[0], k_graph).len() != 1 {
+                break;
+            }
+            if !visited.insert(canonical_kmer(next[0], k_graph)) {
+                break;
+            }
+            unitig.push(next[0]);
+            current = next[0];
+        }
+        unitigs.push(unitig);
+    }
+
+    // nodes starting a unitig in forward (first node) or reverse (rev-compl of last node) orientation
+    let mut unitig_starts: HashMap<IntT, (usize, bool)> = HashMap::with_capacity(2 * unitigs.len());
+    for (i, unitig) in unitigs.iter().enumerate() {
+        unitig_starts.insert(unitig[0], (i, true));
+        unitig_starts.insert(
+            rev_compl_kmer(unitig[unitig.len() - 1], k_graph),
+            (i, false),
+        );
+    }
+
+    let filename = format!("{}_graph.gfa", arguments.output_name);
+    let mut written_links: HashSet<(usize, bool, usize, bool)> = HashSet::new();
+    write_file(&filename, |output| {
+        write_gfa_header(output, data_info)?;
+
+        // write segments with the samples of their k-mers
+        for (i, unitig) in unitigs.iter().enumerate() {
+            let mut sequence = decode_kmer(unitig[0], k_graph);
+            let mut samples = BitSet::with_capacity(data_info.sample_names.len());
+            for window in unitig.windows(2) {
+                sequence.push(get_last_nucl(window[1]));
+                if let Some(kmer_samples) = kmer_2_samples.get(&combine_kmers(window[0], window[1]))
+                {
+                    samples.union_with(kmer_samples);
+                }
+            }
+            // single node -> samples of its outgoing edges
+            if unitig.len() == 1 {
+                for next in successors(all_kmers, unitig[0]) {
+                    if let Some(kmer_samples) = kmer_2_samples.get(&combine_kmers(unitig[0], next))
+                    {
+                        samples.union_with(kmer_samples);
+                    }
+                }
+            }
+            writeln!(
+                output,
+                "S\t{}\t{}\tLN:i:{}\t{}",
+                i + 1,
+                sequence,
+                sequence.len(),
+                colour_tags(&samples)
+            )?;
+        }
+
+        // write links (each edge is stored in both orientations in the graph -> only write one of them)
+        for (i, unitig) in unitigs.iter().enumerate() {
+            let last_nodes = [
+                (unitig[unitig.len() - 1], true),
+                (rev_compl_kmer(unitig[0], k_graph), false),
+            ];
+            for (last_node, orientation) in last_nodes {
+                for next in successors(all_kmers, last_node) {
+                    if let Some(&(j, next_orientation)) = unitig_starts.get(&next) {
+                        let link = (i, orientation, j, next_orientation);
+                        let reverse_link = (j, !next_orientation, i, !orientation);
+                        if written_links.contains(&reverse_link) || !written_links.insert(link) {
+                            continue;
+                        }
+                        let samples = kmer_2_samples
+                            .get(&combine_kmers(last_node, next))
+                            .cloned()
+                            .unwrap_or_default();
+                        writeln!(
+                            output,
+                            "L\t{}\t{}\t{}\t{}\t{}M\t{}",
+                            i + 1,
+                            sign(orientation),
+                            j + 1,
+                            sign(next_orientation),
+                            k_graph - 1,
+                            colour_tags(&samples)
+                        )?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    progress!(
+        arguments.verbose,
+        "     . {} unitigs and {} links written to {}",
+        unitigs.len(),
+        written_links.len(),
+        filename
+    );
+    Ok(())
+}
+
+// export the variant groups (entry k-mer, exit k-mer and branch sequences) in GFA format
+pub fn export_variant_groups_gfa<IntT: KmerInt>(
+    variant_groups: &VariantGroups<IntT>,
+    indel_groups: &VariantGroups<IntT>,
+    kmer_2_samples: &KmerSamples<IntT>,
+    data_info: &DataInfo,
+    arguments: &Config,
+) -> Result<(), SkaloError> {
+    let k_graph = data_info.k_graph;
+
+    // sort groups to get the same output between runs
+    let mut all_groups: Vec<(&(IntT, IntT), &str)> = variant_groups
+        .keys()
+        .map(|key| (key, "snp"))
+        .chain(indel_groups.keys().map(|key| (key, "indel")))
+        .collect();
+    all_groups.sort();
+
+    // sequence of the branches of each group, with the samples of their first k-mer
+    let mut group_branches: Vec<Vec<(String, BitSet)>> = Vec::with_capacity(all_groups.len());
+    for (key, group_type) in &all_groups {
+        let vec_variants = if *group_type == "snp" {
+            &variant_groups[*key]
+        } else {
+            &indel_groups[*key]
+        };
+        let mut branches = Vec::with_capacity(vec_variants.len());
+        for variant in vec_variants {
+            let first_kmer = encode_u8_kmer::<IntT>(&variant.sequence.get_range(0, k_graph + 1))?;
+            branches.push((
+                variant.sequence.decode(),
+                kmer_2_samples.get(&first_kmer).cloned().unwrap_or_default(),
+            ));
+        }
+        group_branches.push(branches);
+    }
+
+    let filename = format!("{}_variant_groups.gfa", arguments.output_name);
+    write_file(&filename, |output| {
+        write_gfa_header(output, data_info)?;
+        for (n, ((key, group_type), branches)) in all_groups.iter().zip(&group_branches).enumerate()
+        {
+            let entry = decode_kmer(key.0, k_graph);
+            let exit = decode_kmer(key.1, k_graph);
+
+            writeln!(
+                output,
+                "S\tg{}_entry\t{}\tLN:i:{}\tty:Z:{}",
+                n + 1,
+                entry,
+                k_graph,
+                group_type
+            )?;
+            writeln!(
+                output,
+                "S\tg{}_exit\t{}\tLN:i:{}\tty:Z:{}",
+                n + 1,
+                exit,
+                k_graph,
+                group_type
+            )?;
+
+            for (m, (sequence, samples)) in branches.iter().enumerate() {
+                writeln!(
+                    output,
+                    "S\tg{}_b{}\t{}\tLN:i:{}\tty:Z:{}\t{}",
+                    n + 1,
+                    m + 1,
+                    sequence,
+                    sequence.len(),
+                    group_type,
+                    colour_tags(samples)
+                )?;
+                writeln!(
+                    output,
+                    "L\tg{}_entry\t+\tg{}_b{}\t+\t{}M",
+                    n + 1,
+                    n + 1,
+                    m + 1,
+                    k_graph
+                )?;
+                if sequence.ends_with(&exit) {
+                    writeln!(
+                        output,
+                        "L\tg{}_b{}\t+\tg{}_exit\t+\t{}M",
+                        n + 1,
+                        m + 1,
+                        n + 1,
+                        k_graph
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    progress!(
+        arguments.verbose,
+        "     . {} variant groups written to {}",
+        all_groups.len(),
+        filename
+    );
+    Ok(())
+}
+
+// GFA header with the index of each sample (used in the colour tags)
+fn write_gfa_header(output: &mut impl Write, data_info: &DataInfo) -> io::Result<()> {
+    writeln!(output, "H\tVN:Z:1.0")?;
+    for (i, name) in data_info.sample_names.iter().enumerate() {
+        writeln!(output, "# sample\t{}\t{}", i, name)?;
+    }
+    Ok(())
+}
+
+// colour tags: number of samples (sc) and sample indexes (cl)
+fn colour_tags(samples: &BitSet) -> String {
+    let indexes: Vec<String> = samples.iter().map(|i| i.to_string()).collect();
+    format!("sc:i:{}\tcl:Z:{}", indexes.len(), indexes.join(","))
+}
+
+fn sign(orientation: bool) -> char {
+    if orientation {
+        '+'
+    } else {
+        '-'
+    }
+}
+
+// distinct successors of a node
+fn successors<IntT: KmerInt>(all_kmers: &KmerGraph<IntT>, kmer: IntT) -> Vec<IntT> {
+    let mut next_kmers: Vec<IntT> = all_kmers.next_kmers(kmer).collect();
+    next_kmers.sort_unstable();
+    next_kmers.dedup();
+    next_kmers
+}
+
+// distinct predecessors of a node (edges are stored in both orientations)
+fn predecessors<IntT: KmerInt>(all_kmers: &KmerGraph<IntT>, kmer: IntT, k: usize) -> Vec<IntT> {
+    successors(all_kmers, rev_compl_kmer(kmer, k))
+        .into_iter()
+        .map(|previous| rev_compl_kmer(previous, k))
+        .collect()
+}
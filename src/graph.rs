@@ -0,0 +1,346 @@
+use bit_set::BitSet;
+use hashbrown::hash_map::DefaultHashBuilder;
+use hashbrown::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use dashmap::ReadOnlyView;
+
+use crate::utils::{rev_compl_kmer, KmerInt};
+
+// colored de Bruijn graph of (k-1)-mers: each node is stored once, as its canonical form (smallest of the
+// (k-1)-mer and its reverse-complement), with its edges as a bitmask of next nucleotides (bits 0-3: A, C, G, T
+// after the canonical form, bits 4-7: A, C, G, T after its reverse-complement)
+pub struct KmerGraph<IntT: KmerInt> {
+    k: usize,
+    mask: IntT,
+    nodes: DashMap<IntT, u8, DefaultHashBuilder>,
+    // edges added by the compaction of the graph, in place of the compacted paths
+    shortcuts: HashMap<IntT, IntT>,
+}
+
+impl<IntT: KmerInt> KmerGraph<IntT> {
+    // empty graph of k-mers of length k (nodes of length k-1)
+    pub fn new(k: usize) -> Self {
+        Self {
+            k: k - 1,
+            mask: (IntT::one() << (2 * (k - 1))) - IntT::one(),
+            nodes: DashMap::with_hasher(DefaultHashBuilder::default()),
+            shortcuts: HashMap::new(),
+        }
+    }
+
+    // add the edge corresponding to a k-mer, in both orientations (can be called from several threads)
+    pub fn add_kmer(&self, kmer: IntT) {
+        self.set_edge(kmer >> 2, kmer.lsb_u8() & 0b11, true);
+        let rc_kmer = rev_compl_kmer(kmer, self.k + 1);
+        self.set_edge(rc_kmer >> 2, rc_kmer.lsb_u8() & 0b11, true);
+    }
+
+    // remove the edge between 2 adjacent nodes (in this orientation only)
+    pub fn remove_edge(&self, kmer: IntT, next_kmer: IntT) {
+        self.set_edge(kmer, next_kmer.lsb_u8() & 0b11, false);
+    }
+
+    // add an edge between 2 distant nodes (end of a compacted path)
+    pub fn add_shortcut(&mut self, kmer: IntT, next_kmer: IntT) {
+        self.shortcuts.insert(kmer, next_kmer);
+    }
+
+    // next nodes of a node (adjacent nodes in k-mer order, then the shortcut if any)
+    pub fn next_kmers(&self, kmer: IntT) -> impl Iterator<Item = IntT> + '_ {
+        let edges = self.edges(kmer);
+        (0..4u8)
+            .filter(move |nucl| (edges >> nucl) & 1 == 1)
+            .map(move |nucl| ((kmer << 2) | IntT::from_encoded_base(nucl)) & self.mask)
+            .chain(self.shortcuts.get(&kmer).copied())
+    }
+
+    pub fn nb_next_kmers(&self, kmer: IntT) -> usize {
+        self.edges(kmer).count_ones() as usize + usize::from(self.shortcuts.contains_key(&kmer))
+    }
+
+    // all nodes, in both orientations
+    pub fn nodes(&self) -> impl Iterator<Item = IntT> + '_ {
+        self.nodes.iter().flat_map(move |entry| {
+            let kmer = *entry.key();
+            let rc_kmer = rev_compl_kmer(kmer, self.k);
+            std::iter::once(kmer).chain((rc_kmer != kmer).then_some(rc_kmer))
+        })
+    }
+
+    // number of distinct (k-1)-mers, without reverse-complements
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // bitmask of the next nucleotides of a node in its orientation
+    fn edges(&self, kmer: IntT) -> u8 {
+        let rc_kmer = rev_compl_kmer(kmer, self.k);
+        if kmer <= rc_kmer {
+            self.nodes.get(&kmer).map_or(0, |edges| *edges & 0b1111)
+        } else {
+            self.nodes.get(&rc_kmer).map_or(0, |edges| *edges >> 4)
+        }
+    }
+
+    fn set_edge(&self, kmer: IntT, next_nucl: u8, present: bool) {
+        let rc_kmer = rev_compl_kmer(kmer, self.k);
+        let (canonical, bit) = if kmer <= rc_kmer {
+            (kmer, 1 << next_nucl)
+        } else {
+            (rc_kmer, 1 << (4 + next_nucl))
+        };
+        if present {
+            *self.nodes.entry(canonical).or_insert(0) |= bit;
+        } else if let Some(mut edges) = self.nodes.get_mut(&canonical) {
+            *edges &= !bit;
+        }
+    }
+}
+
+// samples of each k-mer, stored once for a k-mer and its reverse-complement as the index of its colour set
+// (set of samples) in a table of distinct colour sets
+pub struct KmerSamples<IntT: KmerInt> {
+    k: usize,
+    colours: ReadOnlyView<IntT, u32, DefaultHashBuilder>,
+    colour_sets: Vec<BitSet>,
+    // colour sets of the table no longer used by any k-mer (intermediate unions)
+    nb_removed_colours: usize,
+}
+
+impl<IntT: KmerInt> KmerSamples<IntT> {
+    // colours of the canonical k-mers of length k; the table is compacted to the colour sets still used by a k-mer
+    pub(crate) fn new(
+        k: usize,
+        colours: DashMap<IntT, u32, DefaultHashBuilder>,
+        colour_table: ColourTable,
+    ) -> Self {
+        let all_colour_sets = colour_table.into_colour_sets();
+        let mut is_used = vec![false; all_colour_sets.len()];
+        for colour in colours.iter() {
+            is_used[*colour.value() as usize] = true;
+        }
+
+        // new index of the used colour sets (same order)
+        let mut new_indexes = vec![0; all_colour_sets.len()];
+        let mut colour_sets: Vec<BitSet> = Vec::with_capacity(all_colour_sets.len());
+        for (i, samples) in all_colour_sets.into_iter().enumerate() {
+            if is_used[i] {
+                new_indexes[i] = colour_sets.len() as u32;
+                colour_sets.push(samples);
+            }
+        }
+        colours.alter_all(|_, colour| new_indexes[colour as usize]);
+
+        Self {
+            k,
+            colours: colours.into_read_only(),
+            nb_removed_colours: is_used.len() - colour_sets.len(),
+            colour_sets,
+        }
+    }
+
+    // samples of a k-mer in any orientation
+    pub fn get(&self, kmer: &IntT) -> Option<&BitSet> {
+        self.colour(kmer)
+            .map(|colour| &self.colour_sets[colour as usize])
+    }
+
+    // colour of a k-mer in any orientation: 2 k-mers have the same colour iff they have the same samples
+    pub fn colour(&self, kmer: &IntT) -> Option<u32> {
+        self.colours.get(&canonical_kmer(*kmer, self.k)).copied()
+    }
+
+    // number of k-mers of each sample (k-mer and reverse-complement counted once)
+    pub fn nb_kmers_per_sample(&self, nb_samples: usize) -> Vec<usize> {
+        let mut colour_counts = vec![0; self.colour_sets.len()];
+        for &colour in self.colours.values() {
+            colour_counts[colour as usize] += 1;
+        }
+        let mut counts = vec![0; nb_samples];
+        for (samples, &count) in self.colour_sets.iter().zip(&colour_counts) {
+            for sample in samples {
+                counts[sample] += count;
+            }
+        }
+        counts
+    }
+
+    pub fn nb_colours(&self) -> usize {
+        self.colour_sets.len()
+    }
+
+    pub fn nb_removed_colours(&self) -> usize {
+        self.nb_removed_colours
+    }
+
+    pub fn len(&self) -> usize {
+        self.colours.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colours.is_empty()
+    }
+}
+
+// table of distinct colour sets, filled from several threads: each set is stored once, shared by the index of
+// the sets and the sets of the indexes
+pub(crate) struct ColourTable {
+    indexes: DashMap<Arc<BitSet>, u32, DefaultHashBuilder>,
+    colour_sets: DashMap<u32, Arc<BitSet>, DefaultHashBuilder>,
+    nb_colours: AtomicU32,
+    // index of the union of 2 colour sets already computed
+    unions: DashMap<(u32, u32), u32, DefaultHashBuilder>,
+}
+
+impl ColourTable {
+    pub(crate) fn new() -> Self {
+        Self {
+            indexes: DashMap::with_hasher(DefaultHashBuilder::default()),
+            colour_sets: DashMap::with_hasher(DefaultHashBuilder::default()),
+            nb_colours: AtomicU32::new(0),
+            unions: DashMap::with_hasher(DefaultHashBuilder::default()),
+        }
+    }
+
+    // index of a colour set, added to the table if new
+    pub(crate) fn intern(&self, samples: &BitSet) -> u32 {
+        if let Some(colour) = self.indexes.get(samples) {
+            return *colour;
+        }
+        let samples = Arc::new(samples.clone());
+        *self.indexes.entry(Arc::clone(&samples)).or_insert_with(|| {
+            let colour = self.nb_colours.fetch_add(1, Ordering::Relaxed);
+            self.colour_sets.insert(colour, samples);
+            colour
+        })
+    }
+
+    // index of the union of 2 colour sets
+    pub(crate) fn union(&self, colour_1: u32, colour_2: u32) -> u32 {
+        if colour_1 == colour_2 {
+            return colour_1;
+        }
+        let key = (colour_1.min(colour_2), colour_1.max(colour_2));
+        if let Some(colour) = self.unions.get(&key) {
+            return *colour;
+        }
+        let mut samples = (*self.samples(colour_1)).clone();
+        samples.union_with(&self.samples(colour_2));
+        let colour = self.intern(&samples);
+        self.unions.insert(key, colour);
+        colour
+    }
+
+    fn samples(&self, colour: u32) -> Arc<BitSet> {
+        Arc::clone(&self.colour_sets.get(&colour).unwrap())
+    }
+
+    // colour sets in the order of their indexes
+    pub(crate) fn into_colour_sets(self) -> Vec<BitSet> {
+        drop(self.indexes);
+        let mut colour_sets: Vec<(u32, Arc<BitSet>)> = self.colour_sets.into_iter().collect();
+        colour_sets.sort_unstable_by_key(|(colour, _)| *colour);
+        colour_sets
+            .into_iter()
+            .map(|(_, samples)| Arc::unwrap_or_clone(samples))
+            .collect()
+    }
+}
+
+// smallest of a k-mer and its reverse-complement
+pub fn canonical_kmer<IntT: KmerInt>(kmer: IntT, k: usize) -> IntT {
+    kmer.min(rev_compl_kmer(kmer, k))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{decode_kmer, encode_kmer};
+
+    fn encode(kmer: &str) -> u64 {
+        encode_kmer(kmer).unwrap()
+    }
+
+    // next nodes of a node, decoded
+    fn next_nodes(graph: &KmerGraph<u64>, node: &str) -> Vec<String> {
+        graph
+            .next_kmers(encode(node))
+            .map(|kmer| decode_kmer(kmer, node.len()))
+            .collect()
+    }
+
+    #[test]
+    fn canonical_kmers() {
+        assert_eq!(canonical_kmer(encode("TTTA"), 4), encode("TAAA"));
+        assert_eq!(canonical_kmer(encode("AAAT"), 4), encode("AAAT"));
+        // palindromic k-mer
+        assert_eq!(canonical_kmer(encode("ACGT"), 4), encode("ACGT"));
+        assert_eq!(
+            canonical_kmer(encode("GATTACA"), 7),
+            canonical_kmer(encode("TGTAATC"), 7)
+        );
+    }
+
+    #[test]
+    fn next_kmers_both_orientations() {
+        // nodes of length 4, ACGT is palindromic
+        let mut graph: KmerGraph<u64> = KmerGraph::new(5);
+        for kmer in ["GAACG", "AACGT", "ACGTA"] {
+            graph.add_kmer(encode(kmer));
+        }
+
+        // GAAC, AACG, ACGT and CGTA (with their reverse-complements GTTC, CGTT and TACG)
+        assert_eq!(graph.len(), 4);
+        assert_eq!(graph.nodes().count(), 7);
+
+        assert_eq!(next_nodes(&graph, "GAAC"), vec!["AACG"]);
+        assert_eq!(next_nodes(&graph, "AACG"), vec!["ACGT"]);
+        assert_eq!(next_nodes(&graph, "CGTA"), Vec::<String>::new());
+        // reverse orientation of the k-mers
+        assert_eq!(next_nodes(&graph, "TACG"), vec!["ACGT"]);
+        assert_eq!(next_nodes(&graph, "CGTT"), vec!["GTTC"]);
+        assert_eq!(next_nodes(&graph, "GTTC"), Vec::<String>::new());
+        // both edges of the palindromic node: ACGT -> CGTA and reverse-complement of AACGT (ACGTT)
+        assert_eq!(next_nodes(&graph, "ACGT"), vec!["CGTA", "CGTT"]);
+        assert_eq!(graph.nb_next_kmers(encode("ACGT")), 2);
+
+        // removed edge (in this orientation only) and shortcut
+        graph.remove_edge(encode("ACGT"), encode("CGTA"));
+        assert_eq!(next_nodes(&graph, "ACGT"), vec!["CGTT"]);
+        assert_eq!(next_nodes(&graph, "TACG"), vec!["ACGT"]);
+        graph.add_shortcut(encode("GAAC"), encode("CGTT"));
+        assert_eq!(next_nodes(&graph, "GAAC"), vec!["AACG", "CGTT"]);
+        assert_eq!(graph.nb_next_kmers(encode("GAAC")), 2);
+    }
+
+    #[test]
+    fn colour_table_unions() {
+        let table = ColourTable::new();
+        let samples = |indexes: &[usize]| indexes.iter().copied().collect::<BitSet>();
+
+        let colour_1 = table.intern(&samples(&[0, 2]));
+        let colour_2 = table.intern(&samples(&[1]));
+        assert_eq!((colour_1, colour_2), (0, 1));
+        assert_eq!(table.intern(&samples(&[0, 2])), colour_1);
+
+        // union added once, in any order, and equal to an existing set if any
+        let colour_3 = table.union(colour_1, colour_2);
+        assert_eq!(colour_3, 2);
+        assert_eq!(table.union(colour_2, colour_1), colour_3);
+        assert_eq!(table.union(colour_3, colour_1), colour_3);
+        assert_eq!(table.union(colour_2, colour_2), colour_2);
+        assert_eq!(table.intern(&samples(&[0, 1, 2])), colour_3);
+
+        assert_eq!(
+            table.into_colour_sets(),
+            vec![samples(&[0, 2]), samples(&[1]), samples(&[0, 1, 2])]
+        );
+    }
+}
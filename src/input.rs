@@ -1,45 +1,103 @@
-use hashbrown::HashMap;
+use hashbrown::hash_map::DefaultHashBuilder;
+use hashbrown::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::BufReader;
 //use std::time::Instant;
 use bit_set::BitSet;
 
 use dashmap::DashMap;
 use rayon::prelude::*;
 use rayon::ThreadPoolBuilder;
+use serde::de::{Deserialize, Deserializer, IgnoredAny, MapAccess, Visitor};
 
 use ska::io_utils::load_array;
+use ska::merge_ska_array::MergeSkaArray;
+use ska::merge_ska_dict::{build_and_merge, MergeSkaDict};
 use ska::ska_dict::bit_encoding::decode_kmer;
+use ska::{QualFilter, QualOpts};
 
-use crate::utils::{encode_kmer, rev_compl_u128, CONFIG};
+use crate::error::SkaloError;
+use crate::graph::{canonical_kmer, ColourTable, KmerGraph, KmerSamples};
+use crate::samples::{check_unique_names, keep_selected, read_sample_sheet, select_samples};
+use crate::utils::{encode_kmer, progress, Config, DataInfo, KmerInt};
 
-type KmerGraph = HashMap<u128, Vec<u128>>;
-type KmerSamples = HashMap<u128, BitSet>;
+// the k-mers are encoded with the integer type of the skf file(s), see read_kmer_length()
+// (or of the k-mer length if the split k-mers are built from the sequence files of the samples)
+pub fn read_input_file<IntT: KmerInt>(
+    arguments: &Config,
+) -> Result<(DataInfo, KmerGraph<IntT>, KmerSamples<IntT>), SkaloError> {
+    //let start = Instant::now();
 
+    // read the skf file(s) or build them, and load split-kmers (ska_array), kmer length and sample names
+    let ska_array = if arguments.sequence_files.is_empty() {
+        load_skf_files::<IntT>(arguments)?
+    } else {
+        build_split_kmers::<IntT>(arguments)?
+    };
+    let len_kmer = ska_array.kmer_len();
+    let (lower_mask, upper_mask) = IntT::generate_masks(len_kmer);
+    progress!(arguments.verbose, "     . {}-mers", len_kmer);
 
-pub fn read_input_file() -> (usize, Vec<String>, KmerGraph, KmerSamples) {
+    // display names and metadata of the samples from the sample sheet
+    let original_names = ska_array.names();
+    let sample_sheet = arguments
+        .sample_sheet
+        .as_deref()
+        .map(read_sample_sheet)
+        .transpose()?;
+    let (display_names, metadata) = match &sample_sheet {
+        Some(sheet) => {
+            let nb_absent = original_names
+                .iter()
+                .filter(|name| !sheet.contains(name))
+                .count();
+            if nb_absent > 0 {
+                progress!(
+                    arguments.verbose,
+                    "     . {} samples not in the sample sheet",
+                    nb_absent
+                );
+            }
+            sheet.apply(original_names)
+        }
+        None => (
+            original_names.to_vec(),
+            vec![Vec::new(); original_names.len()],
+        ),
+    };
 
-    let arguments = CONFIG.get().unwrap();
+    // new index of each sample after the inclusion/exclusion of samples (None if excluded)
+    let sample_indexes = select_samples(
+        original_names,
+        &display_names,
+        arguments.include_samples.as_deref(),
+        arguments.exclude_samples.as_deref(),
+        arguments.verbose,
+    )?;
+    let sample_names = keep_selected(display_names, &sample_indexes);
+    let kept_names = keep_selected(original_names.to_vec(), &sample_indexes);
+    let sample_metadata = keep_selected(metadata, &sample_indexes);
 
-    println!(" # read file {}", arguments.input_file);
-    
-    //let start = Instant::now();
-    
-    // read the skf file and load split-kmers (ska_array), kmer length and sample names 
-    let ska_array = load_array::<u128>(&[arguments.input_file.to_string()], arguments.nb_threads).expect("\nerror: could not read the skf file\n\n");
-    let sample_names = ska_array.names().to_vec();    
-    let len_kmer = ska_array.kmer_len();
-    let mask = (1 << (len_kmer * 2)) - 1;
+    check_unique_names(&sample_names)?;
 
-    println!("     . {}-mers", len_kmer);
-    println!("     . {} samples", sample_names.len());
+    progress!(arguments.verbose, "     . {} samples", sample_names.len());
+    if sample_names.len() < sample_indexes.len() {
+        progress!(
+            arguments.verbose,
+            "     . {} samples excluded",
+            sample_indexes.len() - sample_names.len()
+        );
+    }
 
     //let duration = start.elapsed();
     //println!("time taken: {:?}", duration);
-    
-    println!(" # build colored de Bruijn graph");
+
+    progress!(arguments.verbose, " # build colored de Bruijn graph");
 
     //let start = Instant::now();
-    
-    // build De Bruijn graph    
+
+    // build De Bruijn graph
     let degenerate_code: HashMap<u8, Vec<char>> = [
         (b'A', vec!['A']),
         (b'T', vec!['T']),
@@ -56,82 +114,490 @@ pub fn read_input_file() -> (usize, Vec<String>, KmerGraph, KmerSamples) {
         (b'H', vec!['A', 'C', 'T']),
         (b'V', vec!['A', 'C', 'G']),
         (b'N', vec!['A', 'C', 'G', 'T']),
-    ].iter().cloned().collect();
-    
-    let all_kmers: DashMap<u128, Vec<u128>> = DashMap::new();
-    let kmer_samples: DashMap<u128, BitSet> = DashMap::new();
-    
-    ThreadPoolBuilder::new()
+    ]
+    .iter()
+    .cloned()
+    .collect();
+
+    // graph and samples store each k-mer once for both orientations
+    let all_kmers: KmerGraph<IntT> = KmerGraph::new(len_kmer);
+    let kmer_colours: DashMap<IntT, u32, DefaultHashBuilder> =
+        DashMap::with_hasher(DefaultHashBuilder::default());
+    let colour_table = ColourTable::new();
+
+    let pool = ThreadPoolBuilder::new()
         .num_threads(arguments.nb_threads)
-        .build_global()
-        .expect("failed to build the thread pool");
-    
+        .build()
+        .map_err(|e| SkaloError::ThreadPool(e.to_string()))?;
+
     let kmer_iter = ska_array.iter();
-    
-    kmer_iter.par_bridge().for_each(|(int_kmer, int_middle_base_vec)| {
-        let (kmer_left, kmer_right) = decode_kmer(len_kmer, int_kmer, mask, mask);
-
-        // combine samples by middle-base using degenerate code
-        let mut middle_2_samples: HashMap<char, Vec<u16>> = HashMap::with_capacity(4);
-        for (i, nucl) in int_middle_base_vec.iter().enumerate() {
-            if *nucl != 45 {
-                for &new_nucl in &degenerate_code[nucl] {
-                    middle_2_samples
-                        .entry(new_nucl)
-                        .or_insert_with(|| Vec::with_capacity(sample_names.len()))
-                        .push(i as u16);
+
+    pool.install(|| {
+        kmer_iter
+            .par_bridge()
+            .try_for_each(|(int_kmer, int_middle_base_vec)| {
+                let (kmer_left, kmer_right) =
+                    decode_kmer(len_kmer, int_kmer, upper_mask, lower_mask);
+
+                // combine samples by middle-base using degenerate code (k-mers only present in excluded samples are dropped)
+                let mut middle_2_samples: HashMap<char, Vec<u16>> = HashMap::with_capacity(4);
+                // (with mixed calls, 'N' is missing data rather than a mix of the 4 nucleotides)
+                for (nucl, &sample_index) in int_middle_base_vec.iter().zip(&sample_indexes) {
+                    let is_missing = *nucl == 45 || (arguments.mixed_calls && *nucl == b'N');
+                    if let Some(i) = sample_index.filter(|_| !is_missing) {
+                        let new_nucls = degenerate_code
+                            .get(nucl)
+                            .ok_or(SkaloError::InvalidNucleotide(*nucl as char))?;
+                        for &new_nucl in new_nucls {
+                            middle_2_samples
+                                .entry(new_nucl)
+                                .or_insert_with(|| Vec::with_capacity(sample_names.len()))
+                                .push(i as u16);
+                        }
+                    }
+                }
+
+                // build k-mers and save them to the graph
+                let mut bitset_samples = BitSet::with_capacity(sample_names.len());
+                let mut full_kmer = String::with_capacity(len_kmer);
+
+                for (nucl, vec_indexes) in middle_2_samples.iter() {
+                    // crate BitSet of samples
+                    bitset_samples.clear();
+                    bitset_samples.extend(vec_indexes.iter().map(|&x| x as usize));
+
+                    // save k-mers (k-1 in graph and k for samples)
+                    full_kmer.clear();
+                    full_kmer.push_str(&kmer_left);
+                    full_kmer.push(*nucl);
+                    full_kmer.push_str(&kmer_right);
+
+                    let encode_full = encode_kmer(&full_kmer)?;
+                    all_kmers.add_kmer(encode_full);
+
+                    // union of samples if the k-mer is shared by several split k-mers (independent of thread scheduling)
+                    let colour = colour_table.intern(&bitset_samples);
+                    kmer_colours
+                        .entry(canonical_kmer(encode_full, len_kmer))
+                        .and_modify(|previous_colour| {
+                            *previous_colour = colour_table.union(*previous_colour, colour)
+                        })
+                        .or_insert(colour);
                 }
+                Ok::<(), SkaloError>(())
+            })
+    })?;
+
+    let kmer_samples = KmerSamples::new(len_kmer, kmer_colours, colour_table);
+
+    //let duration = start.elapsed();
+    //println!("time taken: {:?}", duration);
+    progress!(arguments.verbose, "     . {} nodes", all_kmers.len());
+    progress!(
+        arguments.verbose,
+        "     . {} colour sets ({} unused sets removed)",
+        kmer_samples.nb_colours(),
+        kmer_samples.nb_removed_colours()
+    );
+
+    let data_info = DataInfo {
+        k_graph: len_kmer - 1,
+        sample_names,
+        original_names: kept_names,
+        metadata_columns: sample_sheet.map_or(Vec::new(), |sheet| sheet.columns),
+        sample_metadata,
+    };
+
+    Ok((data_info, all_kmers, kmer_samples))
+}
+
+// load the skf files and merge them if more than one (samples already loaded from a previous file are ignored)
+fn load_skf_files<IntT: KmerInt>(arguments: &Config) -> Result<MergeSkaArray<IntT>, SkaloError> {
+    let (input_files, nb_threads) = (&arguments.input_files, arguments.nb_threads);
+    if input_files.is_empty() {
+        return Err(SkaloError::InputFile("no input file".to_string()));
+    }
+
+    // single file -> no merging
+    if input_files.len() == 1 {
+        progress!(arguments.verbose, " # read file {}", input_files[0]);
+        return load_array::<IntT>(&[input_files[0].to_string()], nb_threads)
+            .map_err(|e| SkaloError::InputFile(format!("{}: {}", input_files[0], e)));
+    }
+
+    let mut merged_dict: Option<MergeSkaDict<IntT>> = None;
+    let mut loaded_names: HashSet<String> = HashSet::new();
+
+    for input_file in input_files {
+        progress!(arguments.verbose, " # read file {}", input_file);
+
+        let mut ska_array = load_array::<IntT>(&[input_file.to_string()], nb_threads)
+            .map_err(|e| SkaloError::InputFile(format!("{}: {}", input_file, e)))?;
+
+        // k-mer length and strandedness have to be identical between files
+        if let Some(dict) = &merged_dict {
+            if ska_array.kmer_len() != dict.kmer_len() {
+                return Err(SkaloError::InputFile(format!(
+                    "{}: k-mer length ({}) differs from previous file(s) ({})",
+                    input_file,
+                    ska_array.kmer_len(),
+                    dict.kmer_len()
+                )));
+            }
+            if ska_array.rc() != dict.rc() {
+                return Err(SkaloError::InputFile(format!(
+                    "{}: strandedness differs from previous file(s)",
+                    input_file
+                )));
             }
         }
 
-        // build k-mers and save them to the graph
-        let mut bitset_samples = BitSet::with_capacity(sample_names.len());
-        let mut full_kmer = String::with_capacity(len_kmer);
-
-        for (nucl, vec_indexes) in middle_2_samples.iter() {
-            
-            // crate BitSet of samples
-            bitset_samples.clear();
-            bitset_samples.extend(vec_indexes.iter().map(|&x| x as usize));
-            
-            // save k-mers (k-1 in graph and k for samples)
-            full_kmer.clear();
-            full_kmer.push_str(&kmer_left);
-            full_kmer.push(*nucl);
-            full_kmer.push_str(&kmer_right);
-
-            let encoded_kmer_1 = encode_kmer(&full_kmer[..len_kmer - 1]);
-            let encoded_kmer_2 = encode_kmer(&full_kmer[1..]);
-
-            all_kmers
-                .entry(encoded_kmer_1)
-                .or_default()
-                .push(encoded_kmer_2);
-
-            all_kmers
-                .entry(rev_compl_u128(encoded_kmer_2, len_kmer - 1))
-                .or_default()
-                .push(rev_compl_u128(encoded_kmer_1, len_kmer - 1));
-
-            let encode_full = encode_kmer(&full_kmer);
-            kmer_samples
-                .entry(encode_full)
-                .or_insert_with(|| bitset_samples.clone());
-            kmer_samples
-                .entry(rev_compl_u128(encode_full, len_kmer))
-                .or_insert_with(|| bitset_samples.clone());
+        // remove duplicated samples
+        let duplicates: Vec<String> = ska_array
+            .names()
+            .iter()
+            .filter(|name| loaded_names.contains(*name))
+            .cloned()
+            .collect();
+        if duplicates.len() == ska_array.nsamples() {
+            progress!(
+                arguments.verbose,
+                "     . all samples already loaded -> file ignored"
+            );
+            continue;
+        } else if !duplicates.is_empty() {
+            progress!(
+                arguments.verbose,
+                "     . {} sample(s) already loaded -> ignored",
+                duplicates.len()
+            );
+            let vec_duplicates: Vec<&str> = duplicates.iter().map(|name| name.as_str()).collect();
+            ska_array.delete_samples(&vec_duplicates);
         }
-    });
+        loaded_names.extend(ska_array.names().iter().cloned());
 
-    let all_kmers: KmerGraph = all_kmers.into_iter().collect();
-    let kmer_samples: KmerSamples = kmer_samples.into_iter().collect();
+        // merge split k-mers
+        let mut dict = ska_array.to_dict();
+        match merged_dict.as_mut() {
+            Some(merged) => merged.extend(&mut dict),
+            None => merged_dict = Some(dict),
+        }
+    }
 
-    //let duration = start.elapsed();
-    //println!("time taken: {:?}", duration);
-    println!("     . {} nodes", all_kmers.len());
+    Ok(MergeSkaArray::new(&merged_dict.unwrap()))
+}
+
+// build the split k-mers of the samples from their FASTA/FASTQ files with SKA2 (as 'ska build', without skf file)
+fn build_split_kmers<IntT: KmerInt>(arguments: &Config) -> Result<MergeSkaArray<IntT>, SkaloError> {
+    let len_kmer = arguments.kmer_length;
+    if len_kmer.is_multiple_of(2) || len_kmer < 5 || 2 * len_kmer > IntT::n_bits() as usize {
+        return Err(SkaloError::SequenceFile(format!(
+            "invalid k-mer length {} (odd number between 5 and 63)",
+            len_kmer
+        )));
+    }
+
+    // SKA2 stops on missing files
+    for (name, file_1, file_2) in &arguments.sequence_files {
+        for file in std::iter::once(file_1).chain(file_2) {
+            File::open(file)
+                .map_err(|e| SkaloError::SequenceFile(format!("{} ({}): {}", file, name, e)))?;
+        }
+    }
+
+    progress!(
+        arguments.verbose,
+        " # build split k-mers of {} samples",
+        arguments.sequence_files.len()
+    );
 
-    (len_kmer, sample_names, all_kmers, kmer_samples)
+    // quality filters only applied to FASTQ files
+    let quality = QualOpts {
+        min_count: arguments.min_count,
+        min_qual: arguments.min_qual,
+        qual_filter: QualFilter::Strict,
+    };
+    let merged_dict = build_and_merge::<IntT>(
+        &arguments.sequence_files,
+        len_kmer,
+        true,
+        &quality,
+        arguments.nb_threads,
+        None,
+    );
+
+    Ok(MergeSkaArray::new(&merged_dict))
+}
+
+// k-mer length of the skf file(s), read from the beginning of the files without loading the split k-mers:
+// SKA2 encodes the split k-mers with u64 integers if k <= 31 and with u128 integers otherwise
+pub fn read_kmer_length(input_files: &[String]) -> Result<usize, SkaloError> {
+    let mut len_kmer: Option<usize> = None;
+    for input_file in input_files {
+        let file = File::open(input_file)
+            .map_err(|e| SkaloError::InputFile(format!("{}: {}", input_file, e)))?;
+        let reader = snap::read::FrameDecoder::new(BufReader::new(file));
+        let KmerLength(file_len_kmer) = ciborium::de::from_reader(reader)
+            .map_err(|e| SkaloError::InputFile(format!("{}: {}", input_file, e)))?;
+
+        match len_kmer {
+            Some(len) if len != file_len_kmer => {
+                return Err(SkaloError::InputFile(format!(
+                    "{}: k-mer length ({}) differs from previous file(s) ({})",
+                    input_file, file_len_kmer, len
+                )));
+            }
+            _ => len_kmer = Some(file_len_kmer),
+        }
+    }
+    len_kmer.ok_or(SkaloError::InputFile("no input file".to_string()))
+}
+
+// read the fields of a serialised MergeSkaArray until the k-mer length (1st field) and stop there
+struct KmerLength(usize);
+
+impl<'de> Deserialize<'de> for KmerLength {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_map(KmerLengthVisitor)
+            .map(KmerLength)
+    }
+}
+
+struct KmerLengthVisitor;
+
+impl<'de> Visitor<'de> for KmerLengthVisitor {
+    type Value = usize;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a skf file")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<usize, A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "k" {
+                return map.next_value::<usize>();
+            }
+            map.next_value::<IgnoredAny>()?;
+        }
+        Err(serde::de::Error::missing_field("k"))
+    }
+}
+
+// read a file listing input skf files (one per line)
+pub fn read_input_list(file_path: &str) -> Result<Vec<String>, SkaloError> {
+    let content = fs::read_to_string(file_path)
+        .map_err(|e| SkaloError::InputFile(format!("{}: {}", file_path, e)))?;
+    Ok(content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect())
+}
+
+// read a file listing the sequence files of each sample (one sample per line): sample name and FASTA file,
+// or sample name and the 2 FASTQ files of paired reads, separated by tabs or spaces
+pub fn read_fastx_list(
+    file_path: &str,
+) -> Result<Vec<(String, String, Option<String>)>, SkaloError> {
+    let content = fs::read_to_string(file_path)
+        .map_err(|e| SkaloError::SequenceFile(format!("{}: {}", file_path, e)))?;
+
+    let mut sequence_files: Vec<(String, String, Option<String>)> = Vec::new();
+    let mut names: HashSet<String> = HashSet::new();
+    for (i, line) in content.lines().enumerate() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let files = match fields[..] {
+            [] => continue,
+            [_, file] => (file.to_string(), None),
+            [_, file_1, file_2] => (file_1.to_string(), Some(file_2.to_string())),
+            _ => {
+                return Err(SkaloError::SequenceFile(format!(
+                    "{}: line {} should have 2 or 3 fields",
+                    file_path,
+                    i + 1
+                )))
+            }
+        };
+        if !names.insert(fields[0].to_string()) {
+            return Err(SkaloError::SequenceFile(format!(
+                "{}: sample {} listed more than once",
+                file_path, fields[0]
+            )));
+        }
+        sequence_files.push((fields[0].to_string(), files.0, files.1));
+    }
+
+    if sequence_files.is_empty() {
+        return Err(SkaloError::SequenceFile(format!(
+            "{}: no sample",
+            file_path
+        )));
+    }
+    Ok(sequence_files)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("skalo_test_{}_{}", std::process::id(), name))
+    }
+
+    // skf file of samples with a random genome of 500 bp (reproducible LCG) and one SNP per sample
+    fn build_skf<IntT: KmerInt>(
+        file_name: &str,
+        samples: &[&str],
+        len_kmer: usize,
+        rc: bool,
+    ) -> String {
+        let mut state: u64 = 42;
+        let genome: Vec<u8> = (0..500)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                b"ACGT"[(state >> 62) as usize]
+            })
+            .collect();
+
+        let mut fasta_files = Vec::new();
+        for (i, sample) in samples.iter().enumerate() {
+            let mut sequence = genome.clone();
+            let position = 100 + 50 * (sample.as_bytes()[0] as usize % 6);
+            sequence[position] = if sequence[position] == b'A' {
+                b'C'
+            } else {
+                b'A'
+            };
+            let fasta_file = temp_file(&format!("{}_{}.fa", file_name, i));
+            fs::write(
+                &fasta_file,
+                format!(">{}\n{}\n", sample, String::from_utf8(sequence).unwrap()),
+            )
+            .unwrap();
+            fasta_files.push((
+                sample.to_string(),
+                fasta_file.to_str().unwrap().to_string(),
+                None,
+            ));
+        }
+
+        let quality = QualOpts {
+            min_count: 1,
+            min_qual: 0,
+            qual_filter: QualFilter::NoFilter,
+        };
+        let merged_dict = build_and_merge::<IntT>(&fasta_files, len_kmer, rc, &quality, 1, None);
+        let skf_file = temp_file(file_name).to_str().unwrap().to_string();
+        MergeSkaArray::<IntT>::new(&merged_dict)
+            .save(&skf_file)
+            .unwrap();
+        for (_, fasta_file, _) in fasta_files {
+            fs::remove_file(fasta_file).unwrap();
+        }
+        skf_file
+    }
+
+    fn load(input_files: &[&String]) -> Result<MergeSkaArray<u64>, SkaloError> {
+        load_skf_files::<u64>(&Config {
+            input_files: input_files.iter().map(|file| file.to_string()).collect(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn merge_skf_files() {
+        let skf_1 = build_skf::<u64>("merge_1.skf", &["a", "b"], 21, true);
+        let skf_2 = build_skf::<u64>("merge_2.skf", &["c", "b"], 21, true);
+        let skf_3 = build_skf::<u64>("merge_3.skf", &["b", "a"], 21, true);
+        let skf_k17 = build_skf::<u64>("merge_k17.skf", &["d"], 17, true);
+        let skf_no_rc = build_skf::<u64>("merge_no_rc.skf", &["d"], 21, false);
+
+        let single = load(&[&skf_1]);
+        let merged = load(&[&skf_1, &skf_2]);
+        // all samples of the 3rd file already loaded -> file ignored
+        let ignored = load(&[&skf_1, &skf_3, &skf_2]);
+        let different_k = load(&[&skf_1, &skf_k17]);
+        let different_rc = load(&[&skf_1, &skf_no_rc]);
+        let missing = load(&[
+            &skf_1,
+            &temp_file("merge_missing.skf").to_str().unwrap().to_string(),
+        ]);
+        let no_file = load(&[]);
+        for skf_file in [&skf_1, &skf_2, &skf_3, &skf_k17, &skf_no_rc] {
+            fs::remove_file(skf_file).unwrap();
+        }
+
+        // split k-mers of the samples of the 1st file unchanged by the merging
+        let nb_present = |ska_array: &MergeSkaArray<u64>, sample: usize| {
+            ska_array
+                .iter()
+                .filter(|(_, middle_bases)| middle_bases[sample] != b'-')
+                .count()
+        };
+        let single = single.unwrap();
+        assert_eq!(single.names(), &["a", "b"]);
+        let merged = merged.unwrap();
+        assert_eq!(merged.names(), &["a", "b", "c"]);
+        assert_eq!(merged.kmer_len(), 21);
+        assert!(merged.rc());
+        for sample in 0..2 {
+            assert_eq!(nb_present(&merged, sample), nb_present(&single, sample));
+        }
+        assert!(nb_present(&merged, 2) > 0);
+        assert_eq!(ignored.unwrap().names(), &["a", "b", "c"]);
+
+        assert!(matches!(different_k, Err(SkaloError::InputFile(e)) if e.contains("k-mer length")));
+        assert!(
+            matches!(different_rc, Err(SkaloError::InputFile(e)) if e.contains("strandedness"))
+        );
+        assert!(matches!(missing, Err(SkaloError::InputFile(_))));
+        assert!(matches!(no_file, Err(SkaloError::InputFile(_))));
+    }
 
+    #[test]
+    fn kmer_length_of_skf_files() {
+        let skf_21 = build_skf::<u64>("length_21.skf", &["a"], 21, true);
+        let skf_21_bis = build_skf::<u64>("length_21_bis.skf", &["b"], 21, false);
+        let skf_41 = build_skf::<u128>("length_41.skf", &["a"], 41, true);
+        // skf file cut before the k-mer length, and file that is not a skf file
+        let truncated = temp_file("length_truncated.skf");
+        fs::write(&truncated, &fs::read(&skf_21).unwrap()[..10]).unwrap();
+        let invalid = temp_file("length_invalid.skf");
+        fs::write(&invalid, ">a\nACGT\n").unwrap();
+        let (truncated, invalid) = (
+            truncated.to_str().unwrap().to_string(),
+            invalid.to_str().unwrap().to_string(),
+        );
 
+        let files = |files: &[&String]| -> Vec<String> {
+            files.iter().map(|file| file.to_string()).collect()
+        };
+        let length_21 = read_kmer_length(&files(&[&skf_21, &skf_21_bis]));
+        let length_41 = read_kmer_length(&files(&[&skf_41]));
+        let different_k = read_kmer_length(&files(&[&skf_21, &skf_41]));
+        let truncated_file = read_kmer_length(&files(&[&truncated]));
+        let invalid_file = read_kmer_length(&files(&[&skf_21, &invalid]));
+        for file in [&skf_21, &skf_21_bis, &skf_41, &truncated, &invalid] {
+            fs::remove_file(file).unwrap();
+        }
+        let missing_file = read_kmer_length(&files(&[&skf_21]));
+
+        assert_eq!(length_21.unwrap(), 21);
+        assert_eq!(length_41.unwrap(), 41);
+        assert!(matches!(different_k, Err(SkaloError::InputFile(e)) if e.contains("k-mer length")));
+        assert!(
+            matches!(truncated_file, Err(SkaloError::InputFile(e)) if e.starts_with(&truncated))
+        );
+        assert!(matches!(invalid_file, Err(SkaloError::InputFile(e)) if e.starts_with(&invalid)));
+        assert!(matches!(missing_file, Err(SkaloError::InputFile(_))));
+        assert!(matches!(
+            read_kmer_length(&[]),
+            Err(SkaloError::InputFile(_))
+        ));
+    }
+}
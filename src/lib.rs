@@ -0,0 +1,59 @@
+//! skalo: graph-based inference of SNPs and indels from SKA2 split k-mer files.
+//!
+//! The pipeline consists of 4 steps, each taking the configuration (and the dataset
+//! information returned by the first step) explicitly, so it can be run several times
+//! in the same process. The k-mers are encoded with the integer type of the skf file(s): `u64` if
+//! k <= 31 and `u128` otherwise (see [`read_kmer_length`]):
+//!
+//! ```no_run
+//! use skalo::{read_input_file, read_kmer_length, identify_good_kmers, build_variant_groups, analyse_variant_groups, Config, SkaloError};
+//!
+//! # fn main() -> Result<(), SkaloError> {
+//! let config = Config { input_files: vec!["my_file.skf".to_string()], ..Default::default() };
+//! assert!(read_kmer_length(&config.input_files)? > 31);
+//! let (data_info, all_kmers, kmer_samples) = read_input_file::<u128>(&config)?;
+//! let (start_kmers, end_kmers) = identify_good_kmers(&all_kmers, &kmer_samples, &config, &data_info)?;
+//! let (variant_groups, indel_groups) = build_variant_groups(all_kmers, &start_kmers, &end_kmers, &config, &data_info)?;
+//! let variant_calls = analyse_variant_groups(variant_groups, indel_groups, &kmer_samples, &config, &data_info)?;
+//! println!("{} SNPs", variant_calls.snps.len());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Failures are reported as a [`SkaloError`], whose category gives the exit code of the command line tool.
+
+mod annotation;
+mod compaction;
+mod distances;
+mod error;
+mod extremities;
+mod gfa;
+mod graph;
+mod input;
+mod output;
+mod positioning;
+mod process_variants;
+mod qc;
+mod read_graph;
+mod samples;
+mod summary;
+mod tree;
+mod utils;
+
+pub use annotation::{Gene, GeneIndex};
+pub use distances::{DistanceMatrix, SampleClusters};
+pub use error::SkaloError;
+pub use extremities::identify_good_kmers;
+pub use gfa::{export_graph_gfa, export_variant_groups_gfa};
+pub use graph::{KmerGraph, KmerSamples};
+pub use input::{read_fastx_list, read_input_file, read_input_list, read_kmer_length};
+pub use output::create_fasta_and_vcf;
+pub use process_variants::analyse_variant_groups;
+pub use qc::SampleQc;
+pub use read_graph::build_variant_groups;
+pub use summary::{write_run_summary, RunStatistics, RunSummary, StageTiming};
+pub use utils::{
+    ComplexInfo, Config, Contig, DataInfo, DnaSequence, IndelInfo, KmerInt, SnpInfo,
+    UnpositionedIndel, UnpositionedSnp, VariantAnnotation, VariantCalls, VariantGroups,
+    VariantInfo,
+};
@@ -1,24 +1,12 @@
-use std::str;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
+use std::time::Instant;
 
-mod input;
-use input::read_input_file;
-
-mod extremities;
-use extremities::identify_good_kmers;
-
-mod read_graph;
-use read_graph::build_variant_groups;
-
-
-mod compaction;
-mod process_variants;
-mod positioning;
-mod output;
-mod utils;
-use crate::utils::{DATA_INFO, CONFIG, DataInfo, Config};
-
+use skalo::{
+    analyse_variant_groups, build_variant_groups, create_fasta_and_vcf, export_graph_gfa,
+    export_variant_groups_gfa, identify_good_kmers, read_fastx_list, read_input_file,
+    read_input_list, read_kmer_length, write_run_summary, Config, KmerInt, RunSummary, SkaloError,
+};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -27,17 +15,31 @@ use crate::utils::{DATA_INFO, CONFIG, DataInfo, Config};
     about = None,
     long_about = None,
     term_width = 200,
+    subcommand_negates_reqs = true,
+    override_usage = "skalo [OPTIONS] <--input-skf <INPUT_SKF>...|--input-list <INPUT_LIST>>
+        skalo build -f <FASTX_LIST> [OPTIONS]    (split k-mers built from FASTA/FASTQ files, see skalo build --help)",
     help_template = "
 
  Usage: {usage}
 
  input:
-   -i, --input-skf      input SKA2 file
+   -i, --input-skf      input SKA2 file(s), merged if more than one
+   -l, --input-list     file listing input SKA2 files (one per line)
    -r, --reference      reference genome for variant positioning
+   -a, --annotation     GFF3 or GenBank annotation of the reference genome
+       --include-samples  samples to include (file listing names or regex:PATTERN)
+       --exclude-samples  samples to exclude (file listing names or regex:PATTERN)
+       --sample-sheet   TSV file with display names and metadata of samples
 
  output:
    -o, --output-name    prefix of output files [default: skalo]
    -m, --missing        max. fraction of missing data [default: 0.2]
+       --count-missing  count missing data as differences in SNP distances
+       --cluster-threshold  SNP threshold(s) of single-linkage clusters
+       --tree           build a neighbour-joining tree from SNP distances
+       --complex        also report variant groups as MNP/complex variants
+       --mixed-calls    report mixed calls as IUPAC codes instead of N
+       --export-gfa     export graph and variant groups in GFA format
 
  graph traversal:
    -d, --depth          max. depth of recursive paths [default: 4]
@@ -49,68 +51,335 @@ use crate::utils::{DATA_INFO, CONFIG, DataInfo, Config};
 "
 )]
 struct Args {
-    /// input SKA2 file
-    #[arg(short = 'i', long, help_heading = "input")]
-    input_skf: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// input SKA2 file(s)
+    #[arg(short = 'i', long, num_args = 1.., required_unless_present = "input_list", help_heading = "input")]
+    input_skf: Vec<String>,
+
+    /// file listing input SKA2 files
+    #[arg(short = 'l', long, help_heading = "input")]
+    input_list: Option<String>,
 
     /// reference genome for SNP positioning
-    #[arg(short = 'r', long, help_heading = "input")]
+    #[arg(short = 'r', long, global = true, help_heading = "input")]
     reference: Option<PathBuf>,
 
+    /// GFF3 or GenBank annotation of the reference genome
+    #[arg(
+        short = 'a',
+        long,
+        requires = "reference",
+        global = true,
+        help_heading = "input"
+    )]
+    annotation: Option<PathBuf>,
+
+    /// samples to include (file listing their names, or regex:PATTERN matching whole names)
+    #[arg(
+        long,
+        value_name = "LIST_OR_REGEX",
+        global = true,
+        help_heading = "input"
+    )]
+    include_samples: Option<String>,
+
+    /// samples to exclude (file listing their names, or regex:PATTERN matching whole names)
+    #[arg(
+        long,
+        value_name = "LIST_OR_REGEX",
+        global = true,
+        help_heading = "input"
+    )]
+    exclude_samples: Option<String>,
+
+    /// TSV file with the display names and metadata of the samples
+    #[arg(long, global = true, help_heading = "input")]
+    sample_sheet: Option<PathBuf>,
+
     /// prefix of output files
-    #[arg(short = 'o', long, default_value_t = ("skalo").to_string(), help_heading = "output")]
+    #[arg(short = 'o', long, default_value_t = ("skalo").to_string(), global = true, help_heading = "output")]
     output: String,
 
     /// maximum fraction of missing data
-    #[arg(short = 'm', long, default_value_t = 0.2, help_heading = "output")]
+    #[arg(
+        short = 'm',
+        long,
+        default_value_t = 0.2,
+        global = true,
+        help_heading = "output"
+    )]
     missing: f32,
 
+    /// count missing data as differences in SNP distances
+    #[arg(long, global = true, help_heading = "output")]
+    count_missing: bool,
+
+    /// SNP threshold(s) of single-linkage clusters
+    #[arg(long, num_args = 1.., value_delimiter = ',', global = true, help_heading = "output")]
+    cluster_threshold: Vec<usize>,
+
+    /// build a neighbour-joining tree from SNP distances
+    #[arg(long, global = true, help_heading = "output")]
+    tree: bool,
+
+    /// also report variant groups as multi-nucleotide/complex variants
+    #[arg(long, requires = "reference", global = true, help_heading = "output")]
+    complex: bool,
+
+    /// report samples with several alleles as mixed calls (IUPAC codes) instead of N
+    #[arg(long, global = true, help_heading = "output")]
+    mixed_calls: bool,
+
+    /// export graph and variant groups in GFA format
+    #[arg(long, global = true, help_heading = "output")]
+    export_gfa: bool,
+
     /// maximum depth of recursive paths
-    #[arg(short = 'd', long, default_value_t = 4, help_heading = "graph traversal")]
+    #[arg(
+        short = 'd',
+        long,
+        default_value_t = 4,
+        global = true,
+        help_heading = "graph traversal"
+    )]
     depth: usize,
 
     /// maximum number of internal indel k-mers
-    #[arg(short = 'n', long, default_value_t = 2, help_heading = "other")]
+    #[arg(
+        short = 'n',
+        long,
+        default_value_t = 2,
+        global = true,
+        help_heading = "other"
+    )]
     indel_kmers: usize,
 
     /// number of threads
-    #[arg(short = 't', long, default_value_t = 1, help_heading = "other")]
+    #[arg(
+        short = 't',
+        long,
+        default_value_t = 1,
+        global = true,
+        help_heading = "other"
+    )]
     threads: usize,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// build the split k-mers from FASTA/FASTQ files (instead of skf files) and infer variants
+    #[command(help_template = "
+
+ Usage: skalo build -f <FASTX_LIST> [OPTIONS]
+
+ input:
+   -f, --fastx-list     file listing the sequence files of the samples (one per line):
+                        sample name and FASTA file, or sample name and 2 FASTQ files
+   -k, --kmer-length    k-mer length (odd number between 5 and 63) [default: 31]
+       --min-count      min. k-mer count in FASTQ files [default: 5]
+       --min-qual       min. base quality in FASTQ files [default: 20]
+
+ other options (reference genome, samples, output, graph traversal): see skalo --help
+.
+")]
+    Build {
+        /// file listing the sequence files of the samples
+        #[arg(short = 'f', long)]
+        fastx_list: String,
+
+        /// k-mer length
+        #[arg(short = 'k', long, default_value_t = 31)]
+        kmer_length: usize,
+
+        /// minimum k-mer count in FASTQ files
+        #[arg(long, default_value_t = 5)]
+        min_count: u16,
 
+        /// minimum base quality in FASTQ files
+        #[arg(long, default_value_t = 20)]
+        min_qual: u8,
+    },
+}
 
 fn main() {
     println!("\n      skalo v{}     \n", env!("CARGO_PKG_VERSION"));
-    
+
     // get command line arguments
     let args = Args::parse();
-    
-    // initialise the global CONFIG structure
-    CONFIG.set(Config {
-        input_file: args.input_skf.clone(),
+
+    if let Err(error) = run(&args) {
+        eprintln!("\n      Error: {}\n", error);
+        std::process::exit(error.exit_code());
+    }
+}
+
+fn run(args: &Args) -> Result<(), SkaloError> {
+    // collect input files from the command line and the optional list file
+    let mut input_files = args.input_skf.clone();
+    if let Some(input_list) = &args.input_list {
+        input_files.extend(read_input_list(input_list)?);
+    }
+
+    // samples to build the split k-mers from (skalo build)
+    let (sequence_files, kmer_length, min_count, min_qual) = match &args.command {
+        Some(Command::Build {
+            fastx_list,
+            kmer_length,
+            min_count,
+            min_qual,
+        }) => (
+            read_fastx_list(fastx_list)?,
+            *kmer_length,
+            *min_count,
+            *min_qual,
+        ),
+        None => (
+            Vec::new(),
+            Config::default().kmer_length,
+            Config::default().min_count,
+            Config::default().min_qual,
+        ),
+    };
+
+    // initialise the Config structure
+    let mut config = Config {
+        input_files,
+        sequence_files,
+        kmer_length,
+        min_count,
+        min_qual,
+        include_samples: args.include_samples.clone(),
+        exclude_samples: args.exclude_samples.clone(),
+        sample_sheet: args.sample_sheet.clone(),
         output_name: args.output.clone(),
         max_missing: args.missing,
         max_depth: args.depth,
         max_indel_kmers: args.indel_kmers,
         nb_threads: args.threads,
         reference_genome: args.reference.clone(),
-    }).expect("failed to initialise CONFIG");
-    
+        annotation_file: args.annotation.clone(),
+        complex_variants: args.complex,
+        mixed_calls: args.mixed_calls,
+        count_missing: args.count_missing,
+        cluster_thresholds: args.cluster_threshold.clone(),
+        build_tree: args.tree,
+        export_gfa: args.export_gfa,
+        verbose: true,
+        command_line: std::env::args().collect::<Vec<String>>().join(" "),
+    };
+
+    // k-mers are encoded with u64 integers up to k = 31 (as in the skf files), and with u128 integers above
+    let len_kmer = if config.sequence_files.is_empty() {
+        read_kmer_length(&config.input_files)?
+    } else {
+        config.kmer_length
+    };
+    config.kmer_length = len_kmer;
+    if len_kmer <= 31 {
+        run_pipeline::<u64>(&config)
+    } else {
+        run_pipeline::<u128>(&config)
+    }
+}
+
+fn run_pipeline<IntT: KmerInt>(config: &Config) -> Result<(), SkaloError> {
+    // statistics and timings of the run
+    let mut summary = RunSummary::new(config);
+    let start_run = Instant::now();
+
     // read input file
-    let (len_kmer, sample_names, all_kmers, index_map) = read_input_file();
-    
-    // initialise the global DataInfo structure
-    DATA_INFO.set(DataInfo {
-        k_graph: len_kmer -1,
-        sample_names: sample_names.clone(),
-    }).expect("failed to initialise DATA_INFO");    
-    
+    let start = Instant::now();
+    let (data_info, all_kmers, kmer_samples) = read_input_file::<IntT>(config)?;
+    summary.add_timing("read_input", start);
+    summary.statistics.kmer_length = data_info.k_graph + 1;
+    summary.statistics.nb_samples = data_info.sample_names.len();
+    summary.statistics.nb_nodes = all_kmers.len();
+    summary.statistics.nb_colour_sets = kmer_samples.nb_colours();
+
     // identify 'good' kmers in De Bruijn graph
-    let (start_kmers, end_kmers) = identify_good_kmers(&all_kmers, &index_map);
+    let start = Instant::now();
+    let (start_kmers, end_kmers) =
+        identify_good_kmers(&all_kmers, &kmer_samples, config, &data_info)?;
+    summary.add_timing("identify_extremities", start);
+    summary.statistics.nb_entry_nodes = start_kmers.len();
+
+    // export graph before compaction
+    if config.export_gfa {
+        println!(" # export graph");
+        let start = Instant::now();
+        export_graph_gfa(&all_kmers, &kmer_samples, &data_info, config)?;
+        summary.add_timing("export_graph", start);
+    }
 
     // identify variant groups
-    build_variant_groups(all_kmers, start_kmers, end_kmers, index_map);
+    let start = Instant::now();
+    let (variant_groups, indel_groups) =
+        build_variant_groups(all_kmers, &start_kmers, &end_kmers, config, &data_info)?;
+    summary.add_timing("build_variant_groups", start);
+    summary.statistics.nb_variant_groups = variant_groups.len();
+    summary.statistics.nb_indel_groups = indel_groups.len();
 
+    if config.export_gfa {
+        println!(" # export variant groups");
+        let start = Instant::now();
+        export_variant_groups_gfa(
+            &variant_groups,
+            &indel_groups,
+            &kmer_samples,
+            &data_info,
+            config,
+        )?;
+        summary.add_timing("export_variant_groups", start);
+    }
+
+    // infer SNPs and indels
+    let start = Instant::now();
+    let variant_calls = analyse_variant_groups(
+        variant_groups,
+        indel_groups,
+        &kmer_samples,
+        config,
+        &data_info,
+    )?;
+    summary.add_timing("analyse_variant_groups", start);
+    summary.statistics.nb_snps = variant_calls.snps.len();
+    summary.statistics.nb_unpositioned_snps = variant_calls.snps_not_positioned;
+    summary.statistics.nb_snp_collisions = variant_calls
+        .unpositioned_snps
+        .iter()
+        .filter(|snp| snp.reason == "position_collision")
+        .count();
+    summary.statistics.nb_indels = variant_calls.nb_indels;
+    summary.statistics.nb_unpositioned_indels = variant_calls.indels_not_positioned;
+    summary.statistics.nb_complex_variants = variant_calls.complex.len();
+
+    // write output
+    println!(" # write output");
+    let start = Instant::now();
+    create_fasta_and_vcf(variant_calls, &data_info, config)?;
+    summary.add_timing("write_output", start);
+
+    let peak_memory = peak_memory_usage();
+    if let Some(peak_memory) = peak_memory {
+        println!(
+            " # peak memory usage: {:.1} MB",
+            peak_memory as f64 / 1_048_576.0
+        );
+    }
+
+    // write statistics, parameters and timings of the run
+    summary.add_timing("total", start_run);
+    summary.peak_memory_mb = peak_memory.map(|peak_memory| peak_memory as f64 / 1_048_576.0);
+    write_run_summary(&summary, config)?;
+    Ok(())
+}
 
+// maximum resident set size of the process in bytes (only available on Linux)
+fn peak_memory_usage() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let peak_kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(peak_kb * 1024)
 }
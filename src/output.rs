@@ -1,147 +1,552 @@
 use hashbrown::{HashMap, HashSet};
 use std::fs::File;
-use std::io::Write;
+use std::io::{self, BufWriter, Write};
 
-use crate::utils::CONFIG;
+use crate::annotation::annotate_variant;
+use crate::distances::{write_clusters, write_distance_matrix};
+use crate::error::SkaloError;
+use crate::qc::{write_sample_qc_json, write_sample_qc_tsv};
+use crate::samples::{metadata_description, write_sample_report};
+use crate::utils::{
+    is_mixed_call, iupac_nucleotides, Config, Contig, DataInfo, VariantAnnotation, VariantCalls,
+};
 
+// VCF record as (contig index, position, REF, ALT alleles, genotypes, INFO)
+type VcfRecord = (usize, u32, String, Vec<String>, Vec<String>, String);
 
 pub fn create_fasta_and_vcf(
-    genome_name: String,
-    mut genome_seq: Vec<u8>,
-    sample_names: Vec<String>,
-    map: HashMap<u32, Vec<char>>,
-) {
-
-    let arguments = CONFIG.get().unwrap();
-
-    // replace non-ATGCN characters with 'N' in genome_seq
-    for base in genome_seq.iter_mut() {
-        match *base as char {
-            'A' | 'T' | 'G' | 'C' | 'N' => {} // Valid bases remain unchanged
-            _ => *base = b'N', // Replace other characters with 'N'
+    variant_calls: VariantCalls,
+    data_info: &DataInfo,
+    arguments: &Config,
+) -> Result<(), SkaloError> {
+    let sample_names = &data_info.sample_names;
+
+    let VariantCalls {
+        snps: map,
+        indels,
+        complex,
+        mut contigs,
+        genes,
+        unpositioned_snps,
+        unpositioned_indels,
+        distances,
+        clusters,
+        tree,
+        sample_qc,
+        ..
+    } = variant_calls;
+
+    // replace non-ATGCN characters with 'N' in contig sequences
+    for contig in contigs.iter_mut() {
+        for base in contig.seq.iter_mut() {
+            match *base as char {
+                'A' | 'T' | 'G' | 'C' | 'N' => {} // Valid bases remain unchanged
+                _ => *base = b'N',                // Replace other characters with 'N'
+            }
         }
     }
 
-    // sort variants by positions (increasing u32 key)
+    // sort variants by positions (increasing contig index then offset)
     let mut sorted_map: Vec<_> = map.into_iter().collect();
     sorted_map.sort_by_key(|&(key, _)| key);
 
-    let mut sequences: Vec<String> = vec![String::new(); sample_names.len()];
-    let mut genome_alignments: Option<Vec<String>> = None; // only create if genome_seq is not empty
-    let mut vcf_records: Vec<(u32, char, Vec<char>)> = Vec::new();
+    // build SNP-only sequences
+    let mut sequences: Vec<String> =
+        vec![String::with_capacity(sorted_map.len()); sample_names.len()];
+    for (_, snp) in &sorted_map {
+        for (i, &char) in snp.column.iter().enumerate() {
+            sequences[i].push(char);
+        }
+    }
+
+    // write SNP alignment in FASTA format
+    let snp_filename = format!("{}_snps.fas", arguments.output_name);
+    write_file(&snp_filename, |snp_output| {
+        for (i, (name, sequence)) in sample_names.iter().zip(sequences.iter()).enumerate() {
+            writeln!(
+                snp_output,
+                ">{}{}",
+                name,
+                metadata_description(data_info, i)
+            )?;
+            writeln!(snp_output, "{}", sequence)?;
+        }
+        Ok(())
+    })?;
+
+    // write pairwise SNP distances
+    let distances_filename = format!("{}_snp_dists.tsv", arguments.output_name);
+    write_file(&distances_filename, |distances_output| {
+        write_distance_matrix(distances_output, &distances, sample_names)
+    })?;
 
-    if !genome_seq.is_empty() {
-        genome_alignments = Some(vec![String::new(); sample_names.len()]);
+    // write single-linkage clusters of samples
+    if !clusters.is_empty() {
+        let clusters_filename = format!("{}_clusters.tsv", arguments.output_name);
+        write_file(&clusters_filename, |clusters_output| {
+            write_clusters(clusters_output, &clusters, sample_names)
+        })?;
     }
 
-    let mut current_snp_index = 0;
+    // write quality metrics of the samples
+    let qc_filename = format!("{}_sample_qc.tsv", arguments.output_name);
+    write_file(&qc_filename, |qc_output| {
+        write_sample_qc_tsv(qc_output, &sample_qc)
+    })?;
+    let qc_json_filename = format!("{}_sample_qc.json", arguments.output_name);
+    write_file(&qc_json_filename, |qc_json_output| {
+        write_sample_qc_json(qc_json_output, &sample_qc)
+    })?;
+
+    // write the number of mixed calls of each sample
+    if arguments.mixed_calls {
+        let mixed_filename = format!("{}_mixed_sites.tsv", arguments.output_name);
+        write_file(&mixed_filename, |mixed_output| {
+            writeln!(mixed_output, "sample\tmixed_snps\tmixed_indels")?;
+            for (i, (name, sequence)) in sample_names.iter().zip(sequences.iter()).enumerate() {
+                let nb_mixed_snps = sequence.chars().filter(|&c| is_mixed_call(c)).count();
+                let nb_mixed_indels = indels
+                    .iter()
+                    .filter(|indel| indel.genotypes[i] == 'm')
+                    .count();
+                writeln!(
+                    mixed_output,
+                    "{}\t{}\t{}",
+                    name, nb_mixed_snps, nb_mixed_indels
+                )?;
+            }
+            Ok(())
+        })?;
+    }
+
+    // write original names and metadata of the samples
+    if arguments.sample_sheet.is_some() {
+        let samples_filename = format!("{}_samples.tsv", arguments.output_name);
+        write_file(&samples_filename, |samples_output| {
+            write_sample_report(samples_output, data_info)
+        })?;
+    }
+
+    // write neighbour-joining tree
+    if let Some(tree) = tree {
+        let tree_filename = format!("{}_nj_tree.nwk", arguments.output_name);
+        write_file(&tree_filename, |tree_output| {
+            writeln!(tree_output, "{}", tree)
+        })?;
+    }
+
+    // write indels that could not be positioned (all indels if no reference genome)
+    let unpositioned_indels_filename = format!("{}_unpositioned_indels.tsv", arguments.output_name);
+    write_file(
+        &unpositioned_indels_filename,
+        |unpositioned_indels_output| {
+            writeln!(
+                unpositioned_indels_output,
+                "#ID\tREASON\tBRANCH_0\tBRANCH_1\t{}",
+                sample_names.join("\t")
+            )?;
+            for (i, indel) in unpositioned_indels.iter().enumerate() {
+                let column: Vec<String> = indel.column.iter().map(|c| c.to_string()).collect();
+                writeln!(
+                    unpositioned_indels_output,
+                    "unpositioned_indel_{}\t{}\t{}\t{}",
+                    i + 1,
+                    indel.reason,
+                    indel.branches.join("\t"),
+                    column.join("\t")
+                )?;
+            }
+            Ok(())
+        },
+    )?;
+
+    // no reference genome -> no pseudo-genomes nor VCF
+    if contigs.is_empty() {
+        return Ok(());
+    }
 
-    // iterate through genome positions if genome_seq is provided
-    let genome_length = if !genome_seq.is_empty() {
-        genome_seq.len() as u32
-    } else {
-        sorted_map.last().map_or(0, |(pos, _)| *pos + 1)
-    };
+    // write SNPs that could not be positioned (SNP in lower case in the flanking sequence)
+    let unpositioned_filename = format!("{}_unpositioned_snps.tsv", arguments.output_name);
+    write_file(&unpositioned_filename, |unpositioned_output| {
+        writeln!(
+            unpositioned_output,
+            "#ID\tREASON\tOFFSET\tFLANKING_SEQUENCE\t{}",
+            sample_names.join("\t")
+        )?;
+        for (i, snp) in unpositioned_snps.iter().enumerate() {
+            let mut flanking_sequence = snp.flanking_sequence.clone();
+            flanking_sequence.replace_range(
+                snp.offset..snp.offset + 1,
+                &snp.flanking_sequence[snp.offset..snp.offset + 1].to_lowercase(),
+            );
+            let column: Vec<String> = snp.column.iter().map(|c| c.to_string()).collect();
+            writeln!(
+                unpositioned_output,
+                "unpositioned_{}\t{}\t{}\t{}\t{}",
+                i + 1,
+                snp.reason,
+                snp.offset + 1, // 1-based offset
+                flanking_sequence,
+                column.join("\t")
+            )?;
+        }
+        Ok(())
+    })?;
 
-    for pos in 0..genome_length {
-        if current_snp_index < sorted_map.len() && sorted_map[current_snp_index].0 == pos {
-            // SNP position
-            let (snp_pos, vec_chars) = &sorted_map[current_snp_index];
+    // genomic positions deleted in samples carrying a deletion (gaps in pseudo-genomes)
+    let mut deleted_positions: HashMap<(usize, u32), Vec<usize>> = HashMap::new();
+    for indel in &indels {
+        if indel.alt_allele.len() < indel.ref_allele.len()
+            && indel.ref_allele.starts_with(&indel.alt_allele)
+        {
+            let samples: Vec<usize> = indel
+                .genotypes
+                .iter()
+                .enumerate()
+                .filter(|(_, &gt)| gt == '1')
+                .map(|(i, _)| i)
+                .collect();
+            for pos in indel.alt_allele.len()..indel.ref_allele.len() {
+                deleted_positions
+                    .entry((indel.contig, indel.position + pos as u32))
+                    .or_default()
+                    .extend(samples.iter());
+            }
+        }
+    }
 
-            if let Some(ref_genome_alignments) = genome_alignments.as_mut() {
-                let reference_base = genome_seq[*snp_pos as usize] as char;
+    // write pseudo-genomes in FASTA format, one block of sequences per contig
+    // (sequence names are suffixed with the contig name if more than one contig)
+    let genome_filename = format!("{}_pseudo_genomes.fas", arguments.output_name);
+    write_file(&genome_filename, |genome_output| {
+        let mut current_snp_index = 0;
 
-                // push the variant data into vcf_records
-                vcf_records.push((*snp_pos, reference_base, vec_chars.clone()));
+        for (contig_index, contig) in contigs.iter().enumerate() {
+            let mut genome_alignments: Vec<String> =
+                vec![String::with_capacity(contig.seq.len()); sample_names.len()];
 
-                // build genome alignments
-                for (i, &char) in vec_chars.iter().enumerate() {
-                    ref_genome_alignments[i].push(char);
+            for pos in 0..contig.seq.len() as u32 {
+                if current_snp_index < sorted_map.len()
+                    && sorted_map[current_snp_index].0 == (contig_index, pos)
+                {
+                    // SNP position
+                    for (i, &char) in sorted_map[current_snp_index].1.column.iter().enumerate() {
+                        genome_alignments[i].push(char);
+                    }
+                    current_snp_index += 1;
+                } else {
+                    // non-SNP position: add reference base to genome alignment
+                    let ref_base = contig.seq[pos as usize] as char;
+                    for genome_alignment in genome_alignments.iter_mut() {
+                        genome_alignment.push(ref_base);
+                    }
+                    // replace deleted bases by gaps
+                    if let Some(samples) = deleted_positions.get(&(contig_index, pos)) {
+                        for &i in samples {
+                            genome_alignments[i].pop();
+                            genome_alignments[i].push('-');
+                        }
+                    }
                 }
             }
 
-            // build SNP-only sequences
-            for (i, &char) in vec_chars.iter().enumerate() {
-                sequences[i].push(char);
+            for (i, (name, alignment)) in sample_names
+                .iter()
+                .zip(genome_alignments.iter())
+                .enumerate()
+            {
+                let description = metadata_description(data_info, i);
+                if contigs.len() > 1 {
+                    writeln!(genome_output, ">{}|{}{}", name, contig.name, description)?;
+                } else {
+                    writeln!(genome_output, ">{}{}", name, description)?;
+                }
+                writeln!(genome_output, "{}", alignment)?;
             }
+        }
+        Ok(())
+    })?;
 
-            current_snp_index += 1;
+    // VCF records sorted by position
+    let mut vcf_records: Vec<VcfRecord> = Vec::with_capacity(sorted_map.len() + indels.len());
 
-        } else if let Some(ref_genome_alignments) = genome_alignments.as_mut() {
-            // non-SNP position: add reference base to genome alignment
-            let ref_base = genome_seq[pos as usize] as char;
-            for genome_alignment in ref_genome_alignments.iter_mut() {
-                genome_alignment.push(ref_base);
+    for ((contig_index, pos), snp) in &sorted_map {
+        let reference_base = contigs[*contig_index].seq[*pos as usize] as char;
+        let (alt_bases, genotypes) = snp_alleles(reference_base, &snp.column);
+        let info = info_field(&genotypes, alt_bases.len(), &snp.annotation);
+        vcf_records.push((
+            *contig_index,
+            *pos,
+            reference_base.to_string(),
+            alt_bases,
+            genotypes,
+            info,
+        ));
+    }
+
+    // add indels to VCF records and sort all records by position
+    for indel in indels {
+        let genotypes: Vec<String> = indel
+            .genotypes
+            .iter()
+            .map(|&gt| {
+                if gt == 'm' {
+                    "0/1".to_string()
+                } else {
+                    gt.to_string()
+                }
+            })
+            .collect();
+        let info = info_field(&genotypes, 1, &indel.annotation);
+        vcf_records.push((
+            indel.contig,
+            indel.position,
+            indel.ref_allele,
+            vec![indel.alt_allele],
+            genotypes,
+            info,
+        ));
+    }
+
+    // add multi-nucleotide/complex variants (overlapping the SNPs of the same variant group)
+    for variant in complex {
+        let genotypes: Vec<String> = variant
+            .genotypes
+            .iter()
+            .map(|gt| gt.map_or(".".to_string(), |allele| allele.to_string()))
+            .collect();
+        let info =
+            info_field(&genotypes, variant.alt_alleles.len(), &variant.annotation) + ";COMPLEX";
+        vcf_records.push((
+            variant.contig,
+            variant.position,
+            variant.ref_allele,
+            variant.alt_alleles,
+            genotypes,
+            info,
+        ));
+    }
+    vcf_records.sort_by_key(|record| (record.0, record.1));
+
+    // gene annotation of the variants located in a CDS
+    if !genes.is_empty() {
+        for (contig_index, pos, reference, alt_alleles, _, info) in vcf_records.iter_mut() {
+            if let Some(gene_info) = annotate_variant(
+                &genes,
+                &contigs,
+                *contig_index,
+                *pos,
+                reference,
+                alt_alleles,
+            ) {
+                info.push_str(&gene_info);
             }
         }
     }
 
-    // write SNP alignment in FASTA format
-    let snp_filename = format!("{}_snps.fas", arguments.output_name);
-    let mut snp_output = File::create(snp_filename).expect("Unable to create SNP file");
-    for (name, sequence) in sample_names.iter().zip(sequences.iter()) {
-        writeln!(snp_output, ">{}", name).expect("Error writing to SNP file");
-        writeln!(snp_output, "{}", sequence).expect("Error writing to SNP file");
-    }
-
-    if !genome_seq.is_empty() {
-        // write pseudo-genomes in FASTA format
-        let genome_filename = format!("{}_pseudo_genomes.fas", arguments.output_name);
-        let genome_output = File::create(genome_filename).expect("Unable to create genome alignment file");
-        if let Some(ref_genome_alignments) = genome_alignments {
-            for (name, alignment) in sample_names.iter().zip(ref_genome_alignments.iter()) {
-                writeln!(&genome_output, ">{}", name)
-                    .expect("Error writing to genome alignment file");
-                writeln!(&genome_output, "{}", alignment)
-                    .expect("Error writing to genome alignment file");
-            }
+    // write variants in VCF format
+    let vcf_filename = format!("{}_snps.vcf", arguments.output_name);
+    write_file(&vcf_filename, |vcf_output| {
+        write_vcf_header(vcf_output, &contigs, data_info, arguments)?;
+
+        for (contig_index, pos, reference, alt_alleles, genotypes, info) in vcf_records {
+            writeln!(
+                vcf_output,
+                "{}\t{}\t.\t{}\t{}\t.\t.\t{}\tGT\t{}",
+                contigs[contig_index].name,
+                pos + 1, // VCF positions are 1-based
+                reference,
+                alt_alleles.join(","),
+                info,
+                genotypes.join("\t")
+            )?;
         }
+        Ok(())
+    })
+}
 
-        // write variants in VCF format
-        let vcf_filename = format!("{}_snps.vcf", arguments.output_name);
-        let mut vcf_output = File::create(vcf_filename).expect("Unable to create VCF file");
-        writeln!(vcf_output, "##fileformat=VCFv4.2").expect("Error writing VCF header");
+// write VCF meta-information lines and column header
+fn write_vcf_header(
+    vcf_output: &mut impl Write,
+    contigs: &[Contig],
+    data_info: &DataInfo,
+    arguments: &Config,
+) -> io::Result<()> {
+    writeln!(vcf_output, "##fileformat=VCFv4.2")?;
+    writeln!(vcf_output, "##source=skalo v{}", env!("CARGO_PKG_VERSION"))?;
+    if !arguments.command_line.is_empty() {
+        writeln!(vcf_output, "##skaloCommand={}", arguments.command_line)?;
+    }
+    if let Some(reference) = &arguments.reference_genome {
+        let reference_path = reference.canonicalize().unwrap_or(reference.clone());
         writeln!(
             vcf_output,
-            "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\t{}",
-            sample_names.join("\t")
-        )
-        .expect("Error writing VCF header");
-
-        for (pos, reference_base, vec_chars) in vcf_records {
-            let alt_bases: Vec<char> = vec_chars
+            "##reference=file://{}",
+            reference_path.display()
+        )?;
+    }
+    for contig in contigs {
+        writeln!(
+            vcf_output,
+            "##contig=<ID={},length={}>",
+            contig.name,
+            contig.seq.len()
+        )?;
+    }
+    writeln!(vcf_output, "##INFO=<ID=SUP,Number=R,Type=Integer,Description=\"Number of samples supporting each allele (REF first)\">")?;
+    writeln!(vcf_output, "##INFO=<ID=MISS,Number=1,Type=Float,Description=\"Fraction of samples with missing or ambiguous data\">")?;
+    writeln!(vcf_output, "##INFO=<ID=NBR,Number=1,Type=Integer,Description=\"Number of branches in the variant group\">")?;
+    writeln!(vcf_output, "##INFO=<ID=GLEN,Number=1,Type=Integer,Description=\"Sequence length of the variant group\">")?;
+    writeln!(vcf_output, "##INFO=<ID=PV,Number=2,Type=Integer,Description=\"Number of k-mers supporting the position in the selected orientation and in the opposite orientation\">")?;
+    writeln!(vcf_output, "##INFO=<ID=MRG,Number=1,Type=Integer,Description=\"Number of other variant groups merged at this position\">")?;
+    writeln!(vcf_output, "##INFO=<ID=CONFLICT,Number=0,Type=Flag,Description=\"Another variant group at this position calls different nucleotides for some samples (reported in the unpositioned SNPs)\">")?;
+    if arguments.annotation_file.is_some() {
+        writeln!(vcf_output, "##INFO=<ID=GENE,Number=.,Type=String,Description=\"Name of the gene(s) (CDS) containing the variant\">")?;
+        writeln!(vcf_output, "##INFO=<ID=LTAG,Number=.,Type=String,Description=\"Locus tag of the gene(s) containing the variant\">")?;
+        writeln!(vcf_output, "##INFO=<ID=CPOS,Number=.,Type=Integer,Description=\"Position of the variant in its codon (1-3) in each gene\">")?;
+        writeln!(vcf_output, "##INFO=<ID=EFF,Number=.,Type=String,Description=\"Effect of each ALT allele in each gene, gene by gene (synonymous, non_synonymous, stop_gained, stop_lost, frameshift, inframe_indel)\">")?;
+        writeln!(vcf_output, "##INFO=<ID=AA,Number=.,Type=String,Description=\"Amino acid change of each ALT allele in each gene, gene by gene (reference amino acid(s), codon number, alternative amino acid(s))\">")?;
+    }
+    if arguments.complex_variants {
+        writeln!(vcf_output, "##INFO=<ID=COMPLEX,Number=0,Type=Flag,Description=\"Multi-nucleotide or complex variant spanning the variable region of a variant group (also decomposed into SNPs)\">")?;
+    }
+    writeln!(
+        vcf_output,
+        "##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">"
+    )?;
+    // original name and metadata of the samples of the sample sheet
+    if arguments.sample_sheet.is_some() {
+        for (i, name) in data_info.sample_names.iter().enumerate() {
+            write!(
+                vcf_output,
+                "##SAMPLE=<ID={},OriginalName={}",
+                name,
+                vcf_quote(&data_info.original_names[i])
+            )?;
+            for (column, value) in data_info
+                .metadata_columns
                 .iter()
-                .cloned()
-                .filter(|&c| c != reference_base && c != '-' && c != 'N')
-                .collect::<HashSet<_>>() // deduplicate alternative bases
-                .into_iter()
-                .collect();
+                .zip(&data_info.sample_metadata[i])
+            {
+                if !value.is_empty() {
+                    write!(vcf_output, ",{}={}", column, vcf_quote(value))?;
+                }
+            }
+            writeln!(vcf_output, ">")?;
+        }
+    }
+    writeln!(
+        vcf_output,
+        "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\t{}",
+        data_info.sample_names.join("\t")
+    )?;
+    Ok(())
+}
+
+// quoted value of a VCF meta-information line
+fn vcf_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// INFO field of a variant: allele support (from genotypes) and annotations of the variant group
+fn info_field(genotypes: &[String], nb_alt: usize, annotation: &VariantAnnotation) -> String {
+    let mut support = vec![0; nb_alt + 1];
+    // (a sample with a mixed call, e.g. '0/1', supports each of its alleles)
+    for genotype in genotypes {
+        for allele in genotype.split('/') {
+            if let Ok(allele) = allele.parse::<usize>() {
+                if allele <= nb_alt {
+                    support[allele] += 1;
+                }
+            }
+        }
+    }
+    let mut info = format!(
+        "SUP={};MISS={:.3};NBR={};GLEN={};PV={},{}",
+        support
+            .iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+        annotation.ratio_missing,
+        annotation.nb_branches,
+        annotation.group_length,
+        annotation.position_votes.0,
+        annotation.position_votes.1
+    );
+    // SNPs merged from several variant groups
+    if annotation.nb_merged > 0 {
+        info.push_str(&format!(";MRG={}", annotation.nb_merged));
+    }
+    if annotation.conflict {
+        info.push_str(";CONFLICT");
+    }
+    info
+}
 
-            let genotypes: Vec<String> = vec_chars
+// create an output file and write its content (I/O errors are reported with the name of the file)
+pub(crate) fn write_file(
+    filename: &str,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> Result<(), SkaloError> {
+    let output_error = |e: io::Error| SkaloError::Output(format!("{}: {}", filename, e));
+    let mut output = BufWriter::new(File::create(filename).map_err(output_error)?);
+    write(&mut output)
+        .and_then(|_| output.flush())
+        .map_err(output_error)
+}
+
+// get ALT alleles and genotypes of a SNP column given the reference base
+// (mixed calls written as IUPAC codes get a genotype with all their alleles, e.g. '0/1')
+fn snp_alleles(reference_base: char, vec_chars: &[char]) -> (Vec<String>, Vec<String>) {
+    // nucleotides called in each sample (none if missing or ambiguous data)
+    let calls: Vec<Vec<char>> = vec_chars
+        .iter()
+        .map(|&c| {
+            if c == 'N' {
+                Vec::new()
+            } else {
+                iupac_nucleotides(c)
+            }
+        })
+        .collect();
+
+    let mut alt_bases: Vec<char> = calls
+        .iter()
+        .flatten()
+        .cloned()
+        .filter(|&c| c != reference_base)
+        .collect::<HashSet<_>>() // deduplicate alternative bases
+        .into_iter()
+        .collect();
+    alt_bases.sort_unstable(); // same order of ALT alleles between runs
+
+    let genotypes: Vec<String> = calls
+        .iter()
+        .map(|nucls| {
+            // ALT indices in VCF are 1-based
+            let mut alleles: Vec<usize> = nucls
                 .iter()
                 .map(|&c| {
                     if c == reference_base {
-                        "0".to_string()
-                    } else if c == '-' || c == 'N' {
-                        ".".to_string() // missing or ambiguous data
-                    } else if let Some(alt_index) = alt_bases.iter().position(|&alt| alt == c) {
-                        (alt_index + 1).to_string() // ALT indices in VCF are 1-based
+                        0
                     } else {
-                        ".".to_string() // unexpected case
+                        alt_bases.iter().position(|&alt| alt == c).unwrap() + 1
                     }
                 })
                 .collect();
+            alleles.sort_unstable();
+            if alleles.is_empty() {
+                ".".to_string() // missing or ambiguous data
+            } else {
+                alleles
+                    .iter()
+                    .map(|allele| allele.to_string())
+                    .collect::<Vec<_>>()
+                    .join("/")
+            }
+        })
+        .collect();
 
-            writeln!(
-                vcf_output,
-                "{}\t{}\t.\t{}\t{}\t.\t.\t.\tGT\t{}",
-                genome_name,
-                pos + 1, // VCF positions are 1-based
-                reference_base,
-                alt_bases.iter().map(|&c| c.to_string()).collect::<Vec<_>>().join(","),
-                genotypes.join("\t")
-            )
-            .expect("Error writing VCF record");
-        }
-    }
+    (alt_bases.iter().map(|c| c.to_string()).collect(), genotypes)
 }
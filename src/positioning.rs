@@ -1,134 +1,178 @@
 use hashbrown::{HashMap, HashSet};
-use std::fs::File;
 use seq_io::fasta::{Reader, Record};
+use std::cmp::Ordering;
+use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
-use std::cmp::Ordering;
 
 use flate2::read::MultiGzDecoder;
 
-use crate::utils::{encode_kmer, rev_compl, VariantInfo};
+use crate::error::SkaloError;
+use crate::utils::{encode_kmer, rev_compl, Contig, KmerInt, VariantInfo};
 
+// genomic k-mers with their positions as (contig index, offset)
+pub type GenomicKmers<IntT> = HashMap<IntT, Vec<(usize, u32)>>;
 
-// extract genomic k-mers with up to 3 distinct positions
-pub fn extract_genomic_kmers(file_path: PathBuf, k: usize) -> (HashMap<u128, Vec<u32>>, Vec<u8>, String) {
+// extract genomic k-mers with up to 3 distinct positions, as (contig index, offset), from all contigs
+pub fn extract_genomic_kmers<IntT: KmerInt>(
+    file_path: PathBuf,
+    k: usize,
+) -> Result<(GenomicKmers<IntT>, Vec<Contig>), SkaloError> {
     // Initialize HashMap to store k-mers and their positions
-    let mut kmer_map: HashMap<u128, Vec<u32>> = HashMap::new();
+    let mut kmer_map: GenomicKmers<IntT> = HashMap::new();
 
-    // initialize HashSet to track k-mers that have more than 5 positions
-    let mut overflow_kmers: HashSet<u128> = HashSet::new();
+    // initialize HashSet to track k-mers that have more than 3 positions
+    let mut overflow_kmers: HashSet<IntT> = HashSet::new();
 
-    // initialize variables to store the genome
-    let mut genome_seq: Vec<u8> = Vec::new();
-    let mut genome_name: String = "".to_string();
+    // initialize vector to store the contigs of the genome
+    let mut contigs: Vec<Contig> = Vec::new();
 
     // set the reader for compressed or uncompressed files
-    let buf = get_reader(&file_path);
+    let buf = get_reader(&file_path)?;
     let mut reader = Reader::new(buf);
 
-    // initialize sequence counter
-    let mut sequence_count = 0;
-
     // process records one by one
     while let Some(record) = reader.next() {
-        // increment sequence counter
-        sequence_count += 1;
-        if sequence_count > 1 {
-            panic!("\nError: more than one sequence detected in the reference genome file.\n");
-        }
-
         // unwrap record (contains name, sequence, and quality)
         let record_ready = match record {
             Ok(record) => record,
-            Err(_) => continue, // Skip on error
+            Err(error) => {
+                return Err(SkaloError::Reference(format!(
+                    "{}: {}",
+                    file_path.display(),
+                    error
+                )))
+            }
         };
 
-        // process the genome sequence
-        genome_seq = record_ready
+        // process the contig sequence
+        let contig_seq: Vec<u8> = record_ready
             .seq()
             .iter()
             .copied()
             .filter(|&byte| !byte.is_ascii_whitespace())
             .map(|byte| byte.to_ascii_uppercase())
             .collect();
-        
-        // get the genome name
-        genome_name = record_ready.id().unwrap().to_string();
-        
+
+        // get the contig name
+        let contig_name = record_ready
+            .id()
+            .map_err(|_| {
+                SkaloError::Reference(format!("{}: invalid sequence name", file_path.display()))
+            })?
+            .to_string();
+        let contig_index = contigs.len();
+
         // only consider sequences long enough to have a k-mer
-        if genome_seq.len() >= k {
+        if contig_seq.len() >= k {
             // extract k-mers (slices from Vec<u8>)
-            for n in 0..(genome_seq.len() - k +1) {
+            for n in 0..(contig_seq.len() - k + 1) {
                 // get slice of Vec<u8>
-                let kmer = &genome_seq[n..n + k];
+                let kmer = &contig_seq[n..n + k];
 
-                // convert k-mer to u128
-                if let Some(kmer_encoded) = encode_vecu8_u128(kmer) {
+                // convert k-mer to integer
+                if let Some(kmer_encoded) = encode_vecu8_kmer(kmer) {
                     // Skip k-mers that have already overflowed
                     if overflow_kmers.contains(&kmer_encoded) {
                         continue;
                     }
 
-                    // insert or update the k-mer in the HashMap
+                    // insert or update the k-mer in the HashMap, or remove it and add it to the overflow set at its
+                    // 4th position
                     let positions = kmer_map.entry(kmer_encoded).or_insert_with(Vec::new);
                     if positions.len() < 3 {
-                        positions.push((n + k) as u32);
-                    }
-                    
-                    // if positions exceed 3, remove the k-mer and add it to the overflow set
-                    if positions.len() > 3 {
+                        positions.push((contig_index, (n + k) as u32));
+                    } else {
                         kmer_map.remove(&kmer_encoded);
                         overflow_kmers.insert(kmer_encoded);
                     }
                 }
             }
         }
+
+        contigs.push(Contig::new(contig_name, contig_seq));
     }
 
-    (kmer_map, genome_seq, genome_name)
-}
+    if contigs.is_empty() {
+        return Err(SkaloError::Reference(format!(
+            "{}: no sequence found",
+            file_path.display()
+        )));
+    }
 
+    Ok((kmer_map, contigs))
+}
 
 // reader function
-fn get_reader(path: &PathBuf) -> Box<dyn BufRead + Send> {
-    let filename_str = path.to_str().unwrap();
+fn get_reader(path: &PathBuf) -> Result<Box<dyn BufRead + Send>, SkaloError> {
     let file = match File::open(path) {
         Ok(file) => file,
-        Err(error) => panic!("Error opening file: {:?}.", error),
+        Err(error) => {
+            return Err(SkaloError::Reference(format!(
+                "{}: {}",
+                path.display(),
+                error
+            )))
+        }
     };
 
-    if filename_str.ends_with(".gz") {
-        Box::new(BufReader::new(MultiGzDecoder::new(file)))
+    if path.to_string_lossy().ends_with(".gz") {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(file))))
     } else {
-        Box::new(BufReader::new(file))
+        Ok(Box::new(BufReader::new(file)))
     }
 }
 
-
-// encode slice [u8] into a u128
-fn encode_vecu8_u128(kmer: &[u8]) -> Option<u128> {
-    let mut encoded = 0u128;
+// encode slice [u8] into an integer
+fn encode_vecu8_kmer<IntT: KmerInt>(kmer: &[u8]) -> Option<IntT> {
+    let mut encoded = IntT::zero();
     for &base in kmer {
         encoded = (encoded << 2)
-            | match base {
+            | IntT::from_encoded_base(match base {
                 b'A' => 0b00,
                 b'C' => 0b01,
                 b'G' => 0b10,
                 b'T' => 0b11,
                 _ => return None,
-            };
+            });
     }
     Some(encoded)
 }
 
+// structure to hold the genomic position of a bubble
+#[derive(Debug, Clone)]
+pub struct BubblePosition {
+    pub positioned: bool,
+    pub contig: usize,
+    pub position: u32,
+    // "for", "rc" or "none"
+    pub orientation: String,
+    // number of k-mers supporting the most frequent position in each orientation (0 if none or tie)
+    pub votes_forward: usize,
+    pub votes_reverse: usize,
+}
 
+impl BubblePosition {
+    // votes of the selected orientation and of the opposite one
+    pub fn votes(&self) -> (usize, usize) {
+        if self.orientation == "rc" {
+            (self.votes_reverse, self.votes_forward)
+        } else {
+            (self.votes_forward, self.votes_reverse)
+        }
+    }
+}
 
-// returns the genomic position of a bubble
-pub fn scan_variants(vec_variants: &[VariantInfo], len_kmer_graph: usize, kmer_map: &HashMap<u128, Vec<u32>>) -> (bool, u32, String) {
-    let mut final_position = 0;
+// returns the genomic position (contig index and offset) of a bubble
+pub fn scan_variants<IntT: KmerInt>(
+    vec_variants: &[VariantInfo],
+    len_kmer_graph: usize,
+    kmer_map: &GenomicKmers<IntT>,
+) -> Result<BubblePosition, SkaloError> {
+    let mut final_position = (0, 0);
 
-    let mut vec_position_forward: Vec<u32> = Vec::new();
-    let mut vec_position_reverse: Vec<u32> = Vec::new();
+    let mut vec_position_forward: Vec<(usize, u32)> = Vec::new();
+    let mut vec_position_reverse: Vec<(usize, u32)> = Vec::new();
 
     // process only the 1st and last variants
     //let first = &vec_variants[0];
@@ -141,20 +185,20 @@ pub fn scan_variants(vec_variants: &[VariantInfo], len_kmer_graph: usize, kmer_m
 
         // collect positions for the forward sequence
         for pos in 0..=seq.len() - len_kmer_graph {
-            let encoded_kmer = encode_kmer(&seq[pos..pos + len_kmer_graph]);
+            let encoded_kmer = encode_kmer::<IntT>(&seq[pos..pos + len_kmer_graph])?;
             if let Some(vec_pos) = kmer_map.get(&encoded_kmer) {
-                for position in vec_pos {
-                    vec_position_forward.push(position - pos as u32);
+                for (contig, position) in vec_pos {
+                    vec_position_forward.push((*contig, position - pos as u32));
                 }
             }
         }
 
         // collect positions for the reverse-complement sequence
         for pos in 0..=rc_seq.len() - len_kmer_graph {
-            let encoded_kmer = encode_kmer(&rc_seq[pos..pos + len_kmer_graph]);
+            let encoded_kmer = encode_kmer::<IntT>(&rc_seq[pos..pos + len_kmer_graph])?;
             if let Some(vec_pos) = kmer_map.get(&encoded_kmer) {
-                for position in vec_pos {
-                    vec_position_reverse.push(position - pos as u32);
+                for (contig, position) in vec_pos {
+                    vec_position_reverse.push((*contig, position - pos as u32));
                 }
             }
         }
@@ -208,14 +252,19 @@ pub fn scan_variants(vec_variants: &[VariantInfo], len_kmer_graph: usize, kmer_m
         }
         (None, None) => (false, "none".to_string()),
     };
-    
-    (positioned, final_position, final_orientation)
-}
-
 
+    Ok(BubblePosition {
+        positioned,
+        contig: final_position.0,
+        position: final_position.1,
+        orientation: final_orientation,
+        votes_forward: forward.map_or(0, |(_, count)| count),
+        votes_reverse: reverse.map_or(0, |(_, count)| count),
+    })
+}
 
-// returns the most frequent position and its count, or (0, 0) if there's a tie
-fn most_frequent_position(numbers: &[u32]) -> (u32, usize) {
+// returns the most frequent position and its count, or ((0, 0), 0) if there's a tie
+fn most_frequent_position(numbers: &[(usize, u32)]) -> ((usize, u32), usize) {
     let counts = numbers.iter().fold(HashMap::new(), |mut counts, &num| {
         *counts.entry(num).or_insert(0) += 1;
         counts
@@ -240,17 +289,143 @@ fn most_frequent_position(numbers: &[u32]) -> (u32, usize) {
     }
 
     if tie {
-        return (0, 0); // return (0, 0) in case of a tie
+        return ((0, 0), 0); // return ((0, 0), 0) in case of a tie
     }
 
     // if no tie, return the most frequent position and its count
     if let Some(position) = max_element {
         if max_count < 10 {
-            return (0, 0); // return (0, 0) in case of low positioning
+            return ((0, 0), 0); // return ((0, 0), 0) in case of low positioning
         } else {
             return (position, max_count);
         }
     }
 
-    (0, 0) // default return if input is empty
-}
\ No newline at end of file
+    ((0, 0), 0) // default return if input is empty
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::DnaSequence;
+
+    const K: usize = 21;
+
+    // random sequence (linear congruential generator, for reproducible tests)
+    fn random_sequence(length: usize, seed: u64) -> String {
+        let mut state = seed;
+        (0..length)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                ['A', 'C', 'G', 'T'][(state >> 62) as usize]
+            })
+            .collect()
+    }
+
+    fn write_fasta(records: &[(&str, &str)], name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("skalo_test_{}_{}.fa", name, std::process::id()));
+        let content: String = records
+            .iter()
+            .map(|(name, seq)| format!(">{} description\n{}\n", name, seq))
+            .collect();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    // variant group of 2 branches around a SNP in the middle of a sequence
+    fn snp_bubble(sequence: &str) -> Vec<VariantInfo> {
+        let middle = sequence.len() / 2;
+        let alt_base = if &sequence[middle..middle + 1] == "A" {
+            "C"
+        } else {
+            "A"
+        };
+        let alt_sequence = format!(
+            "{}{}{}",
+            &sequence[..middle],
+            alt_base,
+            &sequence[middle + 1..]
+        );
+        [sequence, alt_sequence.as_str()]
+            .iter()
+            .map(|branch| VariantInfo::new(DnaSequence::encode(branch).unwrap(), vec![middle]))
+            .collect()
+    }
+
+    #[test]
+    fn multi_contig_positions() {
+        let chromosome = random_sequence(400, 1);
+        let plasmid = random_sequence(300, 2);
+        let path = write_fasta(
+            &[("chr", &chromosome), ("plasmid", &plasmid.to_lowercase())],
+            "contigs",
+        );
+        let result = extract_genomic_kmers::<u64>(path.clone(), K);
+        std::fs::remove_file(&path).unwrap();
+        let (kmer_map, contigs) = result.unwrap();
+
+        assert_eq!(contigs.len(), 2);
+        assert_eq!(
+            (contigs[0].name.as_str(), contigs[0].seq.len()),
+            ("chr", 400)
+        );
+        assert_eq!(
+            (contigs[1].name.as_str(), contigs[1].seq.as_slice()),
+            ("plasmid", plasmid.as_bytes())
+        );
+
+        // position of the end of each k-mer (offset of the k-mer + k)
+        let kmer = |seq: &str, offset: usize| encode_kmer::<u64>(&seq[offset..offset + K]).unwrap();
+        assert_eq!(kmer_map[&kmer(&chromosome, 0)], vec![(0, K as u32)]);
+        assert_eq!(kmer_map[&kmer(&chromosome, 379)], vec![(0, 400)]);
+        assert_eq!(kmer_map[&kmer(&plasmid, 10)], vec![(1, 10 + K as u32)]);
+
+        // bubble on the second contig, in both orientations
+        let bubble = snp_bubble(&plasmid[100..100 + 2 * K + 1]);
+        let position = scan_variants(&bubble, K, &kmer_map).unwrap();
+        assert!(position.positioned);
+        assert_eq!(
+            (
+                position.contig,
+                position.position,
+                position.orientation.as_str()
+            ),
+            (1, 100 + K as u32, "for")
+        );
+
+        let bubble = snp_bubble(&rev_compl(&plasmid[100..100 + 2 * K + 1]));
+        let position = scan_variants(&bubble, K, &kmer_map).unwrap();
+        assert!(position.positioned);
+        assert_eq!(
+            (
+                position.contig,
+                position.position,
+                position.orientation.as_str()
+            ),
+            (1, 100 + K as u32, "rc")
+        );
+    }
+
+    #[test]
+    fn repeated_kmers() {
+        // poly-A k-mer found 3 times (kept), poly-C k-mer found 4 times (removed)
+        let path = write_fasta(
+            &[("a", &"A".repeat(K + 2)), ("c", &"C".repeat(K + 3))],
+            "repeats",
+        );
+        let result = extract_genomic_kmers::<u64>(path.clone(), K);
+        std::fs::remove_file(&path).unwrap();
+        let (kmer_map, _) = result.unwrap();
+
+        let poly_a = encode_kmer::<u64>(&"A".repeat(K)).unwrap();
+        let poly_c = encode_kmer::<u64>(&"C".repeat(K)).unwrap();
+        assert_eq!(
+            kmer_map[&poly_a],
+            vec![(0, K as u32), (0, K as u32 + 1), (0, K as u32 + 2)]
+        );
+        assert!(!kmer_map.contains_key(&poly_c));
+    }
+}
@@ -1,42 +1,88 @@
+use bit_set::BitSet;
 use hashbrown::{HashMap, HashSet};
 //use std::time::Instant;
-use bit_set::BitSet;
-
-
-use crate::utils::{encode_u8_kmer, rev_compl_u128, get_last_nucl, VariantInfo, CONFIG, DATA_INFO, DataInfo};
-use crate::positioning::{extract_genomic_kmers, scan_variants};
-use crate::output::create_fasta_and_vcf;
 
-type VariantGroups = HashMap<(u128, u128), Vec<VariantInfo>>;
+use crate::annotation::{read_annotation, GeneIndex};
+use crate::distances::{single_linkage_clusters, snp_distances, SampleClusters};
+use crate::error::SkaloError;
+use crate::graph::KmerSamples;
+use crate::positioning::{extract_genomic_kmers, scan_variants, BubblePosition, GenomicKmers};
+use crate::qc::sample_qc;
+use crate::tree::neighbour_joining;
+use crate::utils::{
+    encode_u8_kmer, get_last_nucl, is_mixed_call, iupac_nucleotides, progress, resolve_iupac,
+    rev_compl, rev_compl_kmer, ComplexInfo, Config, Contig, DataInfo, IndelInfo, KmerInt, SnpInfo,
+    UnpositionedIndel, UnpositionedSnp, VariantAnnotation, VariantCalls, VariantGroups,
+    VariantInfo,
+};
 
-
-pub fn analyse_variant_groups(mut variant_groups: VariantGroups, indel_groups: VariantGroups, kmer_2_samples: HashMap<u128, BitSet>) {
-    
-    let arguments = CONFIG.get().unwrap();
-    let data_info = DATA_INFO.get().unwrap();
-        
+pub fn analyse_variant_groups<IntT: KmerInt>(
+    mut variant_groups: VariantGroups<IntT>,
+    indel_groups: VariantGroups<IntT>,
+    kmer_2_samples: &KmerSamples<IntT>,
+    arguments: &Config,
+    data_info: &DataInfo,
+) -> Result<VariantCalls, SkaloError> {
     // check if the optional reference genome file argument is provided -> extract kmers
-    let (do_postioning, kmer_map, genome_name, genome_seq) = if let Some(path) = &arguments.reference_genome {
-        println!(" # read reference genome");
-        let (extracted_kmer_map, seq, name) = extract_genomic_kmers(path.clone(), data_info.k_graph);
-        (true, extracted_kmer_map, name, seq)
+    let (do_postioning, kmer_map, contigs) = if let Some(path) = &arguments.reference_genome {
+        progress!(arguments.verbose, " # read reference genome");
+        let (extracted_kmer_map, contigs) = extract_genomic_kmers(path.clone(), data_info.k_graph)?;
+        progress!(arguments.verbose, "     . {} contig(s)", contigs.len());
+        (true, extracted_kmer_map, contigs)
     } else {
-        (false, HashMap::<u128, Vec<u32>>::new(), "".to_string(), Vec::<u8>::new())
+        (false, GenomicKmers::<IntT>::new(), Vec::<Contig>::new())
+    };
+
+    // read the CDS of the reference genome
+    let genes = match &arguments.annotation_file {
+        Some(path) if do_postioning => {
+            progress!(arguments.verbose, " # read annotation");
+            let genes = read_annotation(path, &contigs, arguments.verbose)?;
+            progress!(arguments.verbose, "     . {} CDS", genes.len());
+            genes
+        }
+        _ => GeneIndex::default(),
     };
-    
-    println!(" # process indels");
-    
+
+    progress!(arguments.verbose, " # process indels");
+
     //let start = Instant::now();
-    
+
     // collect entry kmers of indels
     let (final_indels, entries_indels) = process_indels(indel_groups, data_info.k_graph);
-    println!("     -> {} indels", final_indels.len());
-    
+
+    // infer indel genotypes and position them if reference genome
+    let (indel_records, unpositioned_indels) = build_indel_records(
+        &final_indels,
+        kmer_2_samples,
+        &kmer_map,
+        &contigs,
+        do_postioning,
+        data_info,
+        arguments,
+    )?;
+    let nb_indels = indel_records.len() + unpositioned_indels.len();
+    let indels_not_positioned = if do_postioning {
+        unpositioned_indels.len()
+    } else {
+        0
+    };
+    if do_postioning {
+        progress!(
+            arguments.verbose,
+            "     -> {} indels (+ {} w/o position)",
+            indel_records.len(),
+            indels_not_positioned
+        );
+    } else {
+        progress!(arguments.verbose, "     -> {} indels", nb_indels);
+    }
+
     // remove variants having  internal indels from each variant group
     for (_, vec_variant) in variant_groups.iter_mut() {
         let mut i = 0;
         while i < vec_variant.len() {
-            let nb_indel_kmers = find_internal_indels(&vec_variant[i], &entries_indels, data_info);
+            let nb_indel_kmers = find_internal_indels(&vec_variant[i], &entries_indels, data_info)?;
             // there has to be 4 ends for 2 indels, but reducing the threshold to 3 half the numbers of FPs
             if nb_indel_kmers > arguments.max_indel_kmers {
                 vec_variant.remove(i);
@@ -45,14 +91,15 @@ pub fn analyse_variant_groups(mut variant_groups: VariantGroups, indel_groups: V
             }
         }
     }
-    
+
     //let duration = start.elapsed();
     //println!("time taken: {:?}", duration);
 
-    println!(" # process SNPs");
+    progress!(arguments.verbose, " # process SNPs");
 
     // create a vector of keys sorted by the ratio of size of Vec<VariantInfo> to the length of the first sequence
-    // and sort the keys by decreasing order -> we consider first for snp calling variant group with lot of variants 
+    // and sort the keys by decreasing order -> we consider first for snp calling variant group with lot of variants
+    // (ties are sorted by k-mer values to get the same SNPs between runs)
     let mut sorted_keys: Vec<_> = variant_groups
         .iter()
         .filter_map(|(key, value)| {
@@ -64,157 +111,432 @@ pub fn analyse_variant_groups(mut variant_groups: VariantGroups, indel_groups: V
             })
         })
         .collect();
-    sorted_keys.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap()); // Sort by ratio, descending    
-    
+    sorted_keys.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then_with(|| a.0.cmp(b.0))); // Sort by ratio, descending
+
     //let start = Instant::now();
-    
+
     // start processing SNPs
-    let mut entries_done: HashSet<u128> = HashSet::new();
-    
-    // to store SNPs, with genomic position as key (or counter if no positioning)
-    let mut final_snps: HashMap<u32, Vec<char>> = HashMap::new();
-    let mut not_postioned = 0;
+    let mut entries_done: HashSet<IntT> = HashSet::new();
+
+    // to store SNPs, with genomic position (contig index, offset) as key (or counter if no positioning)
+    let mut final_snps: HashMap<(usize, u32), SnpInfo> = HashMap::new();
+    let mut unpositioned_snps: Vec<UnpositionedSnp> = Vec::new();
     let mut counter = 0;
-        
-    for (key, _) in sorted_keys {
-        if !entries_indels.contains(&key.0) && !entries_indels.contains(&rev_compl_u128(key.1, data_info.k_graph)) {
 
+    for &(key, _) in &sorted_keys {
+        if !entries_indels.contains(&key.0)
+            && !entries_indels.contains(&rev_compl_kmer(key.1, data_info.k_graph))
+        {
             let vec_variants = variant_groups.get(key).unwrap();
 
-            // case with 0 or 1 seq left (ie, not enough to be a variant group)
-            if vec_variants.len() < 2 {
+            // case with 0 or 1 seq left (ie, not enough to be a variant group), or substitution + indel (2 branches of
+            // different lengths, only kept for complex variants)
+            if vec_variants.len() < 2
+                || (vec_variants.len() == 2
+                    && vec_variants[0].sequence.len() != vec_variants[1].sequence.len())
+            {
                 continue;
             }
-            
+
             // check potential SNPs from VariantInfo
             let real_snp_pos = get_potential_snp(vec_variants);
-            
+
             // get SNP column and kmers
-            let mut kmers_to_save: HashSet<u128> = HashSet::new();
+            let mut kmers_to_save: HashSet<IntT> = HashSet::new();
             //let mut found_snp_pos: HashMap<usize, Vec<char>> = HashMap::new();
-            let mut found_snp_pos: HashMap<usize, Vec<char>> = HashMap::with_capacity(real_snp_pos.len());
-            
+            let mut found_snp_pos: Vec<(usize, (Vec<char>, f32))> =
+                Vec::with_capacity(real_snp_pos.len());
+
             for &pos in &real_snp_pos {
                 let mut snp_column = vec!['-'; data_info.sample_names.len()];
-                let mut tmp_kmers: HashSet<u128> = HashSet::new();
-                
+                let mut tmp_kmers: HashSet<IntT> = HashSet::new();
+
                 let mut new_snp = true;
-                
+
                 for variant in vec_variants {
                     let seq = &variant.sequence;
-                    
+
                     // Extract k-mers directly from the packed DNA sequence
-                    let full_before = encode_u8_kmer(&seq.get_range(pos - data_info.k_graph, pos +1));
-                    let full_after = encode_u8_kmer(&seq.get_range(pos, pos + data_info.k_graph +1));
-                    let rc_after = rev_compl_u128(full_after, data_info.k_graph + 1); 
-                    
-                    // this is the critical part: we have to avoid SNPs already identified                 
+                    let full_before =
+                        encode_u8_kmer(&seq.get_range(pos - data_info.k_graph, pos + 1))?;
+                    let full_after =
+                        encode_u8_kmer(&seq.get_range(pos, pos + data_info.k_graph + 1))?;
+                    let rc_after = rev_compl_kmer(full_after, data_info.k_graph + 1);
+
+                    // this is the critical part: we have to avoid SNPs already identified
                     if !entries_done.contains(&full_before) && !entries_done.contains(&rc_after) {
-                        let last_nucl = get_last_nucl(full_before);                        
+                        let last_nucl = get_last_nucl(full_before);
                         let samples = kmer_2_samples.get(&full_before).unwrap();
-                
+
                         for sample_index in samples {
-                            if snp_column[sample_index] == '-' || snp_column[sample_index] == last_nucl {
+                            if snp_column[sample_index] == '-'
+                                || snp_column[sample_index] == last_nucl
+                            {
                                 snp_column[sample_index] = last_nucl;
+                            } else if arguments.mixed_calls {
+                                snp_column[sample_index] =
+                                    resolve_iupac(snp_column[sample_index], last_nucl);
                             } else {
                                 snp_column[sample_index] = 'N';
                             }
                         }
-                        
+
                         // Save k-mers to avoid
                         tmp_kmers.insert(full_before);
-                        tmp_kmers.insert(rev_compl_u128(full_before, data_info.k_graph + 1));
+                        tmp_kmers.insert(rev_compl_kmer(full_before, data_info.k_graph + 1));
                         tmp_kmers.insert(full_after);
-                        tmp_kmers.insert(rc_after);            
-                    
+                        tmp_kmers.insert(rc_after);
                     } else {
                         new_snp = false;
                     }
-                }            
+                }
                 // check level of missing data if new SNP
                 if new_snp {
-                    let (true_variant, ratio_missing) = check_missing_data(data_info.sample_names.len(), &snp_column);
+                    let (true_variant, ratio_missing) =
+                        check_missing_data(data_info.sample_names.len(), &snp_column);
                     if true_variant && ratio_missing <= arguments.max_missing {
                         // save surrounding k-mers
                         kmers_to_save.extend(tmp_kmers);
                         // save SNP
-                        found_snp_pos.insert(pos, snp_column);
-                    }       
-                }            
+                        found_snp_pos.push((pos, (snp_column, ratio_missing)));
+                    }
+                }
             }
             entries_done.extend(kmers_to_save.iter());
-            
+
             // variant positioning if reference genome and if a SNP has been found
             if !found_snp_pos.is_empty() {
+                let seq_length = vec_variants[0].sequence.len();
+                let mut annotation = VariantAnnotation {
+                    nb_branches: vec_variants.len(),
+                    group_length: seq_length,
+                    ..Default::default()
+                };
+
                 if do_postioning {
-                
-                    let (position_found, position, orientation) = scan_variants(vec_variants, data_info.k_graph, &kmer_map);
-                    
-                    if position_found {
-                        let seq_length = vec_variants[0].sequence.len();
-                        let is_forward = orientation == "for";
-                        
+                    let bubble_position =
+                        scan_variants(vec_variants, data_info.k_graph, &kmer_map)?;
+
+                    if bubble_position.positioned {
+                        let (contig, position) = (bubble_position.contig, bubble_position.position);
+                        let is_forward = bubble_position.orientation == "for";
+                        annotation.position_votes = bubble_position.votes();
+
                         // adjust position with SNP pos in variant group and orientation
-                        for (pos, column) in found_snp_pos {
+                        for (pos, (column, ratio_missing)) in found_snp_pos {
                             //println!("{}", seq_length);
-                            
+
                             let final_position = if is_forward {
-                                position + (pos - data_info.k_graph) as u32
+                                (contig, position + (pos - data_info.k_graph) as u32)
                             } else {
-                                position + (seq_length - pos - data_info.k_graph - 1) as u32
+                                (
+                                    contig,
+                                    position + (seq_length - pos - data_info.k_graph - 1) as u32,
+                                )
                             };
-                
+
                             let final_column = if is_forward {
-                                column
+                                column.clone()
                             } else {
-                                complement_snp(&column)
+                                complement_snp(&column)?
                             };
-                            
-                            // save it if position not already taken
-                            if final_snps.contains_key(&final_position) {
-                                not_postioned += 1;
-                            } else {
-                                final_snps.insert(final_position, final_column);
+
+                            // merge with the SNP of another variant group already at this position, or keep the
+                            // saved SNP and report this one if their columns cannot be merged
+                            if let Some(existing_snp) = final_snps.get_mut(&final_position) {
+                                if !merge_snps(
+                                    existing_snp,
+                                    &final_column,
+                                    arguments.mixed_calls,
+                                    arguments.max_missing,
+                                ) {
+                                    unpositioned_snps.push(unpositioned_snp(
+                                        &vec_variants[0],
+                                        pos,
+                                        column,
+                                        "position_collision",
+                                    ));
+                                }
+                                continue;
                             }
-                        } 
+
+                            final_snps.insert(
+                                final_position,
+                                SnpInfo {
+                                    column: final_column,
+                                    annotation: VariantAnnotation {
+                                        ratio_missing,
+                                        ..annotation.clone()
+                                    },
+                                },
+                            );
+                        }
                     } else {
-                        not_postioned += found_snp_pos.len();
+                        // tie between strands or no (unambiguous) hit in the reference genome
+                        let reason = if bubble_position.votes_forward > 0
+                            && bubble_position.votes_forward == bubble_position.votes_reverse
+                        {
+                            "strand_tie"
+                        } else {
+                            "no_hit"
+                        };
+                        for (pos, (column, _)) in found_snp_pos {
+                            unpositioned_snps.push(unpositioned_snp(
+                                &vec_variants[0],
+                                pos,
+                                column,
+                                reason,
+                            ));
+                        }
                     }
                 } else {
-                    for (_, column) in found_snp_pos {
-                         counter += 1;
-                         // save it
-                         final_snps.insert(counter, column);
+                    for (_, (column, ratio_missing)) in found_snp_pos {
+                        counter += 1;
+                        // save it
+                        final_snps.insert(
+                            (0, counter),
+                            SnpInfo {
+                                column,
+                                annotation: VariantAnnotation {
+                                    ratio_missing,
+                                    ..annotation.clone()
+                                },
+                            },
+                        );
                     }
                 }
             }
         }
     }
-    
+
     //let duration = start.elapsed();
     //println!("time taken: {:?}", duration);
 
+    // report the multi-nucleotide/complex variants of each variant group (except plain SNPs), in addition to its
+    // SNPs (a variant group and its reverse-complement are considered once, and so are variants found in several
+    // overlapping variant groups)
+    let mut complex_records: Vec<ComplexInfo> = Vec::new();
+    let mut complex_not_positioned = 0;
+    if arguments.complex_variants && do_postioning {
+        let mut groups_done: HashSet<(IntT, IntT)> = HashSet::new();
+        let mut complex_done: HashSet<(usize, u32, String)> = HashSet::new();
+        for &(key, _) in &sorted_keys {
+            let vec_variants = &variant_groups[key];
+            let rc_key = (
+                rev_compl_kmer(key.1, data_info.k_graph),
+                rev_compl_kmer(key.0, data_info.k_graph),
+            );
+            if vec_variants.len() < 2
+                || entries_indels.contains(&key.0)
+                || entries_indels.contains(&rc_key.0)
+                || is_plain_snp(vec_variants)
+                || !groups_done.insert(*key.min(&rc_key))
+            {
+                continue;
+            }
+
+            let bubble_position = scan_variants(vec_variants, data_info.k_graph, &kmer_map)?;
+            if !bubble_position.positioned {
+                complex_not_positioned += 1;
+                continue;
+            }
+            let annotation = VariantAnnotation {
+                nb_branches: vec_variants.len(),
+                position_votes: bubble_position.votes(),
+                ..Default::default()
+            };
+            for record in build_complex_records(
+                vec_variants,
+                &bubble_position,
+                &contigs,
+                kmer_2_samples,
+                data_info,
+                annotation,
+            )? {
+                if record.annotation.ratio_missing <= arguments.max_missing
+                    && complex_done.insert((
+                        record.contig,
+                        record.position,
+                        record.ref_allele.clone(),
+                    ))
+                {
+                    complex_records.push(record);
+                }
+            }
+        }
+    }
+
     if do_postioning {
-        println!("     -> {} SNPs (+ {} w/o position)", final_snps.len(), not_postioned);
+        let nb_merged = final_snps
+            .values()
+            .filter(|snp| snp.annotation.nb_merged > 0)
+            .count();
+        let nb_conflicts = final_snps
+            .values()
+            .filter(|snp| snp.annotation.conflict)
+            .count();
+        if nb_merged > 0 || nb_conflicts > 0 {
+            progress!(
+                arguments.verbose,
+                "     . {} SNPs merged from several variant groups ({} with conflicting alleles)",
+                nb_merged,
+                nb_conflicts
+            );
+        }
+        let nb_collisions = unpositioned_snps
+            .iter()
+            .filter(|snp| snp.reason == "position_collision")
+            .count();
+        progress!(
+            arguments.verbose,
+            "     -> {} SNPs (+ {} w/o position, {} position collisions)",
+            final_snps.len(),
+            unpositioned_snps.len() - nb_collisions,
+            nb_collisions
+        );
+        if arguments.complex_variants {
+            progress!(
+                arguments.verbose,
+                "     -> {} complex variants (+ {} w/o position)",
+                complex_records.len(),
+                complex_not_positioned
+            );
+        }
     } else {
-        println!("     -> {} SNPs", final_snps.len());
+        progress!(arguments.verbose, "     -> {} SNPs", final_snps.len());
+        if arguments.complex_variants {
+            progress!(
+                arguments.verbose,
+                "     . complex variants are not reported without a reference genome"
+            );
+        }
     }
-    
-    println!(" # write output");    
-    // write output
-    create_fasta_and_vcf(genome_name, genome_seq, data_info.sample_names.clone(), final_snps);
+    if arguments.mixed_calls {
+        let nb_mixed = final_snps
+            .values()
+            .filter(|snp| snp.column.iter().any(|&c| is_mixed_call(c)))
+            .count();
+        progress!(
+            arguments.verbose,
+            "     -> {} SNPs with mixed calls",
+            nb_mixed
+        );
+    }
+
+    // pairwise SNP distances between samples
+    let distances = snp_distances(
+        &final_snps,
+        data_info.sample_names.len(),
+        arguments.count_missing,
+        arguments.nb_threads,
+    )?;
 
-} 
-      
+    // single-linkage clusters of samples at each SNP threshold
+    let clusters: Vec<SampleClusters> = arguments
+        .cluster_thresholds
+        .iter()
+        .map(|&threshold| (threshold, single_linkage_clusters(&distances, threshold)))
+        .collect();
+    for (threshold, cluster_ids) in &clusters {
+        progress!(
+            arguments.verbose,
+            "     -> {} clusters at {} SNPs",
+            cluster_ids.iter().max().unwrap_or(&0),
+            threshold
+        );
+    }
 
-fn find_internal_indels(variant: &VariantInfo, entries_indels: &HashSet<u128>, data_info: &DataInfo) -> usize {
+    // neighbour-joining tree of the samples
+    let tree = if arguments.build_tree {
+        progress!(arguments.verbose, " # build neighbour-joining tree");
+        Some(neighbour_joining(&distances, &data_info.sample_names))
+    } else {
+        None
+    };
+
+    // quality metrics of the samples
+    let kmers_per_sample = kmer_2_samples.nb_kmers_per_sample(data_info.sample_names.len());
+    let sample_qc = sample_qc(
+        &final_snps,
+        &data_info.sample_names,
+        &kmers_per_sample,
+        kmer_2_samples.len(),
+    );
+
+    Ok(VariantCalls {
+        snps: final_snps,
+        indels: indel_records,
+        complex: complex_records,
+        contigs,
+        genes,
+        snps_not_positioned: unpositioned_snps.len(),
+        unpositioned_snps,
+        unpositioned_indels,
+        distances,
+        clusters,
+        tree,
+        sample_qc,
+        nb_indels,
+        indels_not_positioned,
+    })
+}
+
+// SNP that could not be positioned, with the sequence of a branch of its variant group
+fn unpositioned_snp(
+    variant: &VariantInfo,
+    pos: usize,
+    column: Vec<char>,
+    reason: &str,
+) -> UnpositionedSnp {
+    UnpositionedSnp {
+        flanking_sequence: variant.sequence.decode(),
+        offset: pos,
+        column,
+        reason: reason.to_string(),
+    }
+}
+
+// merge the SNP column of a variant group into the SNP already saved at the same position: missing data is
+// filled and, with mixed calls, different nucleotides are combined into their IUPAC code; the saved SNP is left
+// unchanged if the columns conflict (different nucleotides for a sample) or if the merged column does not pass the
+// filters, and false is returned
+fn merge_snps(snp: &mut SnpInfo, column: &[char], mixed_calls: bool, max_missing: f32) -> bool {
+    let mut merged_column = snp.column.clone();
+    for (saved, &new) in merged_column.iter_mut().zip(column) {
+        if new == '-' || *saved == new {
+            continue;
+        }
+        if *saved == '-' {
+            *saved = new;
+        } else if mixed_calls {
+            *saved = resolve_iupac(*saved, new);
+        } else if *saved != 'N' && new != 'N' {
+            snp.annotation.conflict = true;
+            return false;
+        }
+    }
+    let (is_variable, ratio_missing) = check_missing_data(column.len(), &merged_column);
+    if !is_variable || ratio_missing > max_missing {
+        return false;
+    }
+    snp.column = merged_column;
+    snp.annotation.ratio_missing = ratio_missing;
+    snp.annotation.nb_merged += 1;
+    true
+}
+
+fn find_internal_indels<IntT: KmerInt>(
+    variant: &VariantInfo,
+    entries_indels: &HashSet<IntT>,
+    data_info: &DataInfo,
+) -> Result<usize, SkaloError> {
     let mut nb = 0;
     let sequence = &variant.sequence;
     let k_graph = data_info.k_graph;
 
     // precompute the initial k-mer
-    let mut kmer = encode_u8_kmer(&sequence.get_range(0, k_graph));
-    let mask = (1u128 << (2 * k_graph)) - 1; // Mask for retaining k-mer length
+    let mut kmer = encode_u8_kmer::<IntT>(&sequence.get_range(0, k_graph))?;
+    let mask = (IntT::one() << (2 * k_graph)) - IntT::one(); // Mask for retaining k-mer length
 
     // sliding window for k-mer computation
     for i in k_graph..sequence.len() {
@@ -223,7 +545,7 @@ fn find_internal_indels(variant: &VariantInfo, entries_indels: &HashSet<u128>, d
             let byte_index = i / 4;
             let shift = 6 - (i % 4) * 2;
             let bits = (sequence.data[byte_index] >> shift) & 0b11;
-            bits as u128
+            IntT::from_encoded_base(bits)
         };
         kmer = ((kmer << 2) | next_nucleotide) & mask; // update k-mer with new nucl
 
@@ -231,30 +553,41 @@ fn find_internal_indels(variant: &VariantInfo, entries_indels: &HashSet<u128>, d
             nb += 1;
         }
     }
-    nb
+    Ok(nb)
 }
 
-    
-fn process_indels(indel_groups: VariantGroups, k_graph: usize) -> (VariantGroups, HashSet<u128>) {    
-    let mut entries_indels: HashSet<u128> = HashSet::new();
-    let mut final_indels: VariantGroups = HashMap::new();
-    
-    for (combined_ext, vec_variant) in &indel_groups {
-        
+fn process_indels<IntT: KmerInt>(
+    indel_groups: VariantGroups<IntT>,
+    k_graph: usize,
+) -> (VariantGroups<IntT>, HashSet<IntT>) {
+    let mut entries_indels: HashSet<IntT> = HashSet::new();
+    let mut final_indels: VariantGroups<IntT> = HashMap::new();
+
+    // sorted iteration -> same selection between a group and its reverse-complement in all runs
+    let mut sorted_keys: Vec<&(IntT, IntT)> = indel_groups.keys().collect();
+    sorted_keys.sort();
+
+    for combined_ext in sorted_keys {
+        let vec_variant = indel_groups.get(combined_ext).unwrap();
+
         if !entries_indels.contains(&combined_ext.0) {
-            
             // test if rev-compl exists
-            let rc_1 = rev_compl_u128(combined_ext.0, k_graph);
-            let rc_2 = rev_compl_u128(combined_ext.1, k_graph);
+            let rc_1 = rev_compl_kmer(combined_ext.0, k_graph);
+            let rc_2 = rev_compl_kmer(combined_ext.1, k_graph);
             let rc_combined = (rc_2, rc_1);
-            
+
             if indel_groups.contains_key(&rc_combined) {
-                
                 // compare the size of variant groups and select the shortest one
                 // this is equivalent to indel realigning in read-alignment (useful in repeats)
                 let vec_variant2 = indel_groups.get(&rc_combined).unwrap();
-                let sum1: usize = vec_variant.iter().map(|variant| variant.sequence.len()).sum();
-                let sum2: usize = vec_variant2.iter().map(|variant| variant.sequence.len()).sum();
+                let sum1: usize = vec_variant
+                    .iter()
+                    .map(|variant| variant.sequence.len())
+                    .sum();
+                let sum2: usize = vec_variant2
+                    .iter()
+                    .map(|variant| variant.sequence.len())
+                    .sum();
                 if sum1 <= sum2 {
                     final_indels.insert(*combined_ext, vec_variant.clone());
                 } else {
@@ -267,28 +600,460 @@ fn process_indels(indel_groups: VariantGroups, k_graph: usize) -> (VariantGroups
             entries_indels.insert(rc_1);
             entries_indels.insert(combined_ext.1);
             entries_indels.insert(rc_2);
-        } 
+        }
     }
     (final_indels, entries_indels)
 }
 
+// build indel records: sample genotypes from the first k-mer of each branch, then left-anchored REF/ALT alleles
+// returns the positioned indels and the indels w/o position (all indels if no reference genome) passing the
+// missing data filter
+fn build_indel_records<IntT: KmerInt>(
+    final_indels: &VariantGroups<IntT>,
+    kmer_2_samples: &KmerSamples<IntT>,
+    kmer_map: &GenomicKmers<IntT>,
+    contigs: &[Contig],
+    do_postioning: bool,
+    data_info: &DataInfo,
+    arguments: &Config,
+) -> Result<(Vec<IndelInfo>, Vec<UnpositionedIndel>), SkaloError> {
+    let k_graph = data_info.k_graph;
+    let nb_samples = data_info.sample_names.len();
+
+    let mut indel_records: Vec<IndelInfo> = Vec::new();
+    let mut unpositioned_indels: Vec<UnpositionedIndel> = Vec::new();
 
+    // sort indel groups to get the same records between runs
+    let mut sorted_keys: Vec<&(IntT, IntT)> = final_indels.keys().collect();
+    sorted_keys.sort();
+
+    for key in sorted_keys {
+        let vec_variants = final_indels.get(key).unwrap();
+        if vec_variants.len() != 2 {
+            continue;
+        }
+
+        // allele column ('0' or '1' = index of the branch, 'N' = both, '-' = none)
+        let mut column = vec!['-'; nb_samples];
+        for (allele, variant) in vec_variants.iter().enumerate() {
+            let allele_char = if allele == 0 { '0' } else { '1' };
+            let first_kmer = encode_u8_kmer::<IntT>(&variant.sequence.get_range(0, k_graph + 1))?;
+            if let Some(samples) = kmer_2_samples.get(&first_kmer) {
+                for sample_index in samples {
+                    if column[sample_index] == '-' || column[sample_index] == allele_char {
+                        column[sample_index] = allele_char;
+                    } else {
+                        column[sample_index] = 'N';
+                    }
+                }
+            }
+        }
+
+        // check both alleles are present and level of missing data (with mixed calls, samples with both alleles
+        // are not missing and carry both alleles)
+        let is_missing = |c: char| c == '-' || (c == 'N' && !arguments.mixed_calls);
+        let nb_missing = column.iter().filter(|&&c| is_missing(c)).count();
+        let has_allele = |allele: char| {
+            column
+                .iter()
+                .any(|&c| c == allele || (c == 'N' && arguments.mixed_calls))
+        };
+        if !has_allele('0')
+            || !has_allele('1')
+            || nb_missing as f32 / nb_samples as f32 > arguments.max_missing
+        {
+            continue;
+        }
+
+        let mut unpositioned = |reason: &str| {
+            unpositioned_indels.push(UnpositionedIndel {
+                branches: vec_variants
+                    .iter()
+                    .map(|variant| variant.sequence.decode())
+                    .collect(),
+                column: column.clone(),
+                reason: reason.to_string(),
+            })
+        };
+        if !do_postioning {
+            unpositioned("no_reference");
+            continue;
+        }
+
+        let bubble_position = scan_variants(vec_variants, k_graph, kmer_map)?;
+        let (contig, position, orientation) = (
+            bubble_position.contig,
+            bubble_position.position,
+            &bubble_position.orientation,
+        );
+        if !bubble_position.positioned || (position as usize) < k_graph {
+            // tie between strands or no (unambiguous) hit in the reference genome
+            if bubble_position.votes_forward > 0
+                && bubble_position.votes_forward == bubble_position.votes_reverse
+            {
+                unpositioned("strand_tie");
+            } else {
+                unpositioned("no_hit");
+            }
+            continue;
+        }
+
+        // get both branches in the orientation of the reference genome
+        let branches: Vec<String> = vec_variants
+            .iter()
+            .map(|variant| {
+                let seq = variant.sequence.decode();
+                if orientation == "for" {
+                    seq
+                } else {
+                    rev_compl(&seq)
+                }
+            })
+            .collect();
+
+        // the reference allele is the branch identical to the genome at this position
+        let start = position as usize - k_graph;
+        let ref_index = branches.iter().position(|branch| {
+            contigs[contig].seq.get(start..start + branch.len()) == Some(branch.as_bytes())
+        });
+
+        let ref_index = match ref_index {
+            Some(index) => index,
+            None => {
+                unpositioned("no_reference_allele");
+                continue;
+            }
+        };
 
-fn get_potential_snp(vec_variant: &Vec<VariantInfo>) -> HashSet<usize> {
+        let (offset, ref_allele, alt_allele) =
+            trim_alleles(&branches[ref_index], &branches[1 - ref_index]);
+
+        let ref_char = if ref_index == 0 { '0' } else { '1' };
+        let genotypes: Vec<char> = column
+            .iter()
+            .map(|&c| match c {
+                '0' | '1' if c == ref_char => '0',
+                '0' | '1' => '1',
+                'N' if arguments.mixed_calls => 'm',
+                _ => '.',
+            })
+            .collect();
+
+        indel_records.push(IndelInfo {
+            contig,
+            position: (start + offset) as u32,
+            ref_allele,
+            alt_allele,
+            genotypes,
+            annotation: VariantAnnotation {
+                ratio_missing: nb_missing as f32 / nb_samples as f32,
+                nb_branches: vec_variants.len(),
+                group_length: branches[ref_index].len(),
+                position_votes: bubble_position.votes(),
+                ..Default::default()
+            },
+        });
+    }
+    Ok((indel_records, unpositioned_indels))
+}
+
+// variant group whose branches have the same length and differ at a single position (reported as a SNP only)
+fn is_plain_snp(vec_variants: &[VariantInfo]) -> bool {
+    let branches: Vec<Vec<u8>> = vec_variants
+        .iter()
+        .map(|variant| variant.sequence.decode().into_bytes())
+        .collect();
+    if branches
+        .iter()
+        .any(|branch| branch.len() != branches[0].len())
+    {
+        return false;
+    }
+    (0..branches[0].len())
+        .filter(|&i| branches.iter().any(|branch| branch[i] != branches[0][i]))
+        .count()
+        == 1
+}
+
+// build the multi-nucleotide/complex variants of a positioned variant group, in the orientation of the reference
+// genome: branches of the same length sharing a run of k_graph bases go through a common node, so each variable
+// region between such runs is a variant (except plain SNPs); branches of different lengths (substitution + indel)
+// make a single variant from the whole branches, the reference allele being the branch identical to the genome
+fn build_complex_records<IntT: KmerInt>(
+    vec_variants: &[VariantInfo],
+    bubble_position: &BubblePosition,
+    contigs: &[Contig],
+    kmer_2_samples: &KmerSamples<IntT>,
+    data_info: &DataInfo,
+    annotation: VariantAnnotation,
+) -> Result<Vec<ComplexInfo>, SkaloError> {
+    let k_graph = data_info.k_graph;
+
+    // branches in the orientation of the reference genome
+    let is_forward = bubble_position.orientation == "for";
+    let branches: Vec<String> = vec_variants
+        .iter()
+        .map(|variant| {
+            let seq = variant.sequence.decode();
+            if is_forward {
+                seq
+            } else {
+                rev_compl(&seq)
+            }
+        })
+        .collect();
+    let Some(start) = (bubble_position.position as usize).checked_sub(k_graph) else {
+        return Ok(Vec::new());
+    };
+    let genome = &contigs[bubble_position.contig].seq;
+    let group_annotation = VariantAnnotation {
+        group_length: branches[0].len(),
+        ..annotation
+    };
+
+    if branches
+        .iter()
+        .any(|branch| branch.len() != branches[0].len())
+    {
+        let Some(ref_seq) = branches
+            .iter()
+            .find(|branch| genome.get(start..start + branch.len()) == Some(branch.as_bytes()))
+        else {
+            return Ok(Vec::new());
+        };
+        let regions: Vec<(usize, usize)> =
+            branches.iter().map(|branch| (0, branch.len())).collect();
+        let record = build_complex_record(
+            vec_variants,
+            &branches,
+            &regions,
+            ref_seq,
+            start,
+            bubble_position,
+            kmer_2_samples,
+            data_info,
+            group_annotation,
+        )?;
+        return Ok(record.into_iter().collect());
+    }
+
+    // variable regions separated by at least k_graph bases shared by all branches
+    let branch_bytes: Vec<&[u8]> = branches.iter().map(|branch| branch.as_bytes()).collect();
+    let mut regions: Vec<(usize, usize)> = Vec::new();
+    for i in (0..branch_bytes[0].len()).filter(|&i| {
+        branch_bytes
+            .iter()
+            .any(|branch| branch[i] != branch_bytes[0][i])
+    }) {
+        match regions.last_mut() {
+            Some(region) if i - region.1 < k_graph => region.1 = i + 1,
+            _ => regions.push((i, i + 1)),
+        }
+    }
+
+    let mut records: Vec<ComplexInfo> = Vec::with_capacity(regions.len());
+    for (region_start, region_end) in regions {
+        let Some(ref_seq) = genome.get(start + region_start..start + region_end) else {
+            continue;
+        };
+        let ref_seq = String::from_utf8_lossy(ref_seq).to_string();
+        let branch_regions = vec![(region_start, region_end); branches.len()];
+        let record = build_complex_record(
+            vec_variants,
+            &branches,
+            &branch_regions,
+            &ref_seq,
+            start + region_start,
+            bubble_position,
+            kmer_2_samples,
+            data_info,
+            group_annotation.clone(),
+        )?;
+        records.extend(record);
+    }
+    Ok(records)
+}
+
+// build a multi-nucleotide/complex variant from a region of each branch (start and end in the orientation of the
+// reference genome); alleles are trimmed as indels and the genotype of a sample comes from the k-mers overlapping
+// the region of a branch (None if plain SNP or less than 2 alleles present)
+#[allow(clippy::too_many_arguments)]
+fn build_complex_record<IntT: KmerInt>(
+    vec_variants: &[VariantInfo],
+    branches: &[String],
+    regions: &[(usize, usize)],
+    ref_seq: &str,
+    ref_start: usize,
+    bubble_position: &BubblePosition,
+    kmer_2_samples: &KmerSamples<IntT>,
+    data_info: &DataInfo,
+    annotation: VariantAnnotation,
+) -> Result<Option<ComplexInfo>, SkaloError> {
+    let k_graph = data_info.k_graph;
+    let nb_samples = data_info.sample_names.len();
+    let is_forward = bubble_position.orientation == "for";
+
+    // alleles of the branches (0 = reference allele)
+    let mut alt_seqs: Vec<String> = Vec::new();
+    let mut branch_alleles: Vec<usize> = Vec::with_capacity(branches.len());
+    for (branch, &(region_start, region_end)) in branches.iter().zip(regions) {
+        let allele = &branch[region_start..region_end];
+        if allele == ref_seq {
+            branch_alleles.push(0);
+        } else if let Some(index) = alt_seqs.iter().position(|alt| alt == allele) {
+            branch_alleles.push(index + 1);
+        } else {
+            alt_seqs.push(allele.to_string());
+            branch_alleles.push(alt_seqs.len());
+        }
+    }
+    let (offset, ref_allele, alt_alleles) = trim_complex_alleles(ref_seq, &alt_seqs);
+    if alt_alleles.is_empty()
+        || (ref_allele.len() == 1 && alt_alleles.iter().all(|alt| alt.len() == 1))
+    {
+        return Ok(None);
+    }
+
+    // allele of each sample from the k-mers overlapping the region of the branches (None if different alleles or none)
+    let mut genotypes: Vec<Option<usize>> = vec![None; nb_samples];
+    let mut ambiguous = vec![false; nb_samples];
+    for ((variant, &allele), &(region_start, region_end)) in
+        vec_variants.iter().zip(&branch_alleles).zip(regions)
+    {
+        // region in the orientation of the branch
+        let length = variant.sequence.len();
+        let (region_start, region_end) = if is_forward {
+            (region_start, region_end)
+        } else {
+            (length - region_end, length - region_start)
+        };
+        let mut branch_samples: Option<BitSet> = None;
+        for i in region_start.saturating_sub(k_graph)..region_end.min(length - k_graph) {
+            let kmer = encode_u8_kmer::<IntT>(&variant.sequence.get_range(i, i + k_graph + 1))?;
+            let samples = kmer_2_samples.get(&kmer).cloned().unwrap_or_default();
+            match branch_samples.as_mut() {
+                Some(branch_samples) => branch_samples.intersect_with(&samples),
+                None => branch_samples = Some(samples),
+            }
+        }
+        for sample_index in branch_samples.unwrap_or_default().iter() {
+            ambiguous[sample_index] |= genotypes[sample_index].is_some_and(|saved| saved != allele);
+            genotypes[sample_index] = Some(allele);
+        }
+    }
+    for (genotype, &is_ambiguous) in genotypes.iter_mut().zip(&ambiguous) {
+        if is_ambiguous {
+            *genotype = None;
+        }
+    }
+
+    // at least 2 alleles have to be present
+    let alleles_present: HashSet<usize> = genotypes.iter().flatten().copied().collect();
+    if alleles_present.len() < 2 {
+        return Ok(None);
+    }
+
+    Ok(Some(ComplexInfo {
+        contig: bubble_position.contig,
+        position: (ref_start + offset) as u32,
+        ref_allele,
+        alt_alleles,
+        annotation: VariantAnnotation {
+            ratio_missing: genotypes.iter().filter(|call| call.is_none()).count() as f32
+                / nb_samples as f32,
+            ..annotation
+        },
+        genotypes,
+    }))
+}
+
+// trim the shared suffix then the shared prefix of all the alleles of a complex variant, keeping 1 left-anchoring
+// base if the alleles have different lengths (as in trim_alleles()); returns the offset and the trimmed alleles
+fn trim_complex_alleles(ref_seq: &str, alt_seqs: &[String]) -> (usize, String, Vec<String>) {
+    let alleles: Vec<&[u8]> = std::iter::once(ref_seq)
+        .chain(alt_seqs.iter().map(|alt| alt.as_str()))
+        .map(|allele| allele.as_bytes())
+        .collect();
+
+    let mut suffix = 0;
+    while alleles.iter().all(|allele| {
+        allele.len() - suffix > 1
+            && allele[allele.len() - suffix - 1] == alleles[0][alleles[0].len() - suffix - 1]
+    }) {
+        suffix += 1;
+    }
+    let ends: Vec<usize> = alleles.iter().map(|allele| allele.len() - suffix).collect();
+
+    let shared = |i: usize| {
+        alleles
+            .iter()
+            .zip(&ends)
+            .all(|(allele, &end)| i < end && allele[i] == alleles[0][i])
+    };
+    let mut offset = 0;
+    if ends.iter().all(|&end| end == ends[0]) {
+        while ends[0] - offset > 1 && shared(offset) {
+            offset += 1;
+        }
+    } else {
+        while ends.iter().all(|&end| end - offset > 1) && shared(offset) && shared(offset + 1) {
+            offset += 1;
+        }
+    }
+
+    let alt_alleles = alt_seqs
+        .iter()
+        .zip(&ends[1..])
+        .map(|(alt, &end)| alt[offset..end].to_string())
+        .collect();
+    (offset, ref_seq[offset..ends[0]].to_string(), alt_alleles)
+}
+
+// trim shared suffix then shared prefix of 2 alleles while keeping 1 left-anchoring base
+// (this left-aligns indels in repeats); returns the offset of the anchoring base and the trimmed alleles
+fn trim_alleles(ref_seq: &str, alt_seq: &str) -> (usize, String, String) {
+    let ref_bytes = ref_seq.as_bytes();
+    let alt_bytes = alt_seq.as_bytes();
+
+    let mut ref_end = ref_bytes.len();
+    let mut alt_end = alt_bytes.len();
+    while ref_end > 1 && alt_end > 1 && ref_bytes[ref_end - 1] == alt_bytes[alt_end - 1] {
+        ref_end -= 1;
+        alt_end -= 1;
+    }
+
+    let mut offset = 0;
+    while ref_end - offset > 1
+        && alt_end - offset > 1
+        && ref_bytes[offset] == alt_bytes[offset]
+        && ref_bytes[offset + 1] == alt_bytes[offset + 1]
+    {
+        offset += 1;
+    }
+
+    (
+        offset,
+        ref_seq[offset..ref_end].to_string(),
+        alt_seq[offset..alt_end].to_string(),
+    )
+}
+
+// returns the sorted positions of the SNPs in the variant group
+fn get_potential_snp(vec_variant: &Vec<VariantInfo>) -> Vec<usize> {
     let mut snps_set = HashSet::new();
     // collect all SNPs into the HashSet
     for variant in vec_variant {
         snps_set.extend(&variant.vec_snps);
     }
-    
-    let mut actual_snps_set = HashSet::new();
+
+    let mut actual_snps = Vec::new();
 
     // check which positions in snps_set are actual SNPs
     for &pos in &snps_set {
         let mut nucleotide_presence = [false; 4]; // A, C, G, T
 
         for variant in vec_variant {
-            let seq = &variant.sequence;            
+            let seq = &variant.sequence;
             if pos < seq.len() {
                 let nucl = seq.get_range(pos, pos + 1)[0];
                 match nucl {
@@ -304,25 +1069,30 @@ fn get_potential_snp(vec_variant: &Vec<VariantInfo>) -> HashSet<usize> {
         // count the number of distinct nucleotides
         let distinct_count = nucleotide_presence.iter().filter(|&&x| x).count();
         if distinct_count > 1 {
-            actual_snps_set.insert(pos);
+            actual_snps.push(pos);
         }
     }
-    actual_snps_set
+    actual_snps.sort_unstable();
+    actual_snps
 }
 
-
 pub fn check_missing_data(nb_total: usize, snp_column: &[char]) -> (bool, f32) {
-    // count occurrences of valid SNPs (A, T, G, C) and calculate missing data
+    // count occurrences of valid SNPs (A, T, G, C, or IUPAC code of a mixed call) and calculate missing data
     let mut nucleotide_counts = [false; 4];
     let mut missing_samples = 0;
 
     for &snp in snp_column {
-        match snp {
-            'A' => nucleotide_counts[0] = true,
-            'T' => nucleotide_counts[1] = true,
-            'G' => nucleotide_counts[2] = true,
-            'C' => nucleotide_counts[3] = true,
-            _ => missing_samples += 1,
+        if snp == '-' || snp == 'N' {
+            missing_samples += 1;
+            continue;
+        }
+        for nucl in iupac_nucleotides(snp) {
+            match nucl {
+                'A' => nucleotide_counts[0] = true,
+                'T' => nucleotide_counts[1] = true,
+                'G' => nucleotide_counts[2] = true,
+                _ => nucleotide_counts[3] = true,
+            }
         }
     }
 
@@ -335,18 +1105,267 @@ pub fn check_missing_data(nb_total: usize, snp_column: &[char]) -> (bool, f32) {
     (valid_nucleotide_count >= 2, ratio_missing)
 }
 
-
-fn complement_snp(dna: &[char]) -> Vec<char> {
+fn complement_snp(dna: &[char]) -> Result<Vec<char>, SkaloError> {
     dna.iter()
         .map(|&nucleotide| match nucleotide {
-            'A' => 'T',
-            'T' => 'A',
-            'C' => 'G',
-            'G' => 'C',
-            '-' => '-',
-            'N' => 'N',
-            _ => panic!("Invalid nucleotide: {}", nucleotide),
+            'A' => Ok('T'),
+            'T' => Ok('A'),
+            'C' => Ok('G'),
+            'G' => Ok('C'),
+            '-' => Ok('-'),
+            'N' => Ok('N'),
+            // IUPAC codes of mixed calls
+            'R' => Ok('Y'),
+            'Y' => Ok('R'),
+            'S' => Ok('S'),
+            'W' => Ok('W'),
+            'K' => Ok('M'),
+            'M' => Ok('K'),
+            'B' => Ok('V'),
+            'V' => Ok('B'),
+            'D' => Ok('H'),
+            'H' => Ok('D'),
+            _ => Err(SkaloError::InvalidNucleotide(nucleotide)),
         })
         .collect()
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extremities::identify_good_kmers;
+    use crate::graph::{canonical_kmer, ColourTable, KmerGraph};
+    use crate::read_graph::build_variant_groups;
+    use dashmap::DashMap;
+    use hashbrown::hash_map::DefaultHashBuilder;
+
+    const K: usize = 21;
+
+    // random sequence (linear congruential generator, for reproducible tests)
+    fn random_sequence(length: usize) -> Vec<u8> {
+        let mut state: u64 = 7;
+        (0..length)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                b"ACGT"[(state >> 62) as usize]
+            })
+            .collect()
+    }
+
+    // genomes of 6 samples: SNPs shared or not between samples, and a deletion in 2 samples
+    fn sample_genomes() -> Vec<Vec<u8>> {
+        let genome = random_sequence(3000);
+        let substitute = |genome: &mut Vec<u8>, position: usize, shift: usize| {
+            let index = b"ACGT".iter().position(|&b| b == genome[position]).unwrap();
+            genome[position] = b"ACGT"[(index + shift) % 4];
+        };
+        let mut genomes = vec![genome; 6];
+        for (sample, position, shift) in [
+            (1, 500, 1),
+            (2, 500, 1),
+            (1, 1500, 1),
+            (3, 1500, 2),
+            (2, 2200, 3),
+            (4, 2600, 1),
+            (5, 800, 2),
+        ] {
+            substitute(&mut genomes[sample], position, shift);
+        }
+        for sample in [3, 4] {
+            genomes[sample].drain(1000..1002);
+        }
+        genomes
+    }
+
+    // colored de Bruijn graph of the k-mers of the genomes
+    fn build_graph(genomes: &[Vec<u8>]) -> (KmerGraph<u64>, KmerSamples<u64>) {
+        let graph: KmerGraph<u64> = KmerGraph::new(K);
+        let mut kmer_samples: HashMap<u64, BitSet> = HashMap::new();
+        for (sample, genome) in genomes.iter().enumerate() {
+            for window in genome.windows(K) {
+                let kmer: u64 = encode_u8_kmer(window).unwrap();
+                graph.add_kmer(kmer);
+                kmer_samples
+                    .entry(canonical_kmer(kmer, K))
+                    .or_default()
+                    .insert(sample);
+            }
+        }
+        let colour_table = ColourTable::new();
+        let colours = DashMap::with_hasher(DefaultHashBuilder::default());
+        for (kmer, samples) in kmer_samples {
+            colours.insert(kmer, colour_table.intern(&samples));
+        }
+        (graph, KmerSamples::new(K, colours, colour_table))
+    }
+
+    // variants inferred from the genomes, as sorted text lines (variant groups inserted in reverse order if 'shuffle')
+    fn variant_calls(genomes: &[Vec<u8>], arguments: &Config, shuffle: bool) -> Vec<String> {
+        let names: Vec<String> = (0..genomes.len()).map(|i| format!("s{}", i)).collect();
+        let data_info = DataInfo {
+            k_graph: K - 1,
+            sample_names: names.clone(),
+            original_names: names,
+            metadata_columns: Vec::new(),
+            sample_metadata: vec![Vec::new(); genomes.len()],
+        };
+        let (graph, kmer_samples) = build_graph(genomes);
+        let (start_kmers, end_kmers) =
+            identify_good_kmers(&graph, &kmer_samples, arguments, &data_info).unwrap();
+        let (mut variant_groups, mut indel_groups) =
+            build_variant_groups(graph, &start_kmers, &end_kmers, arguments, &data_info).unwrap();
+        if shuffle {
+            let reinsert = |groups: VariantGroups<u64>| {
+                let mut new_groups = VariantGroups::with_capacity(4 * groups.len());
+                new_groups.extend(groups.into_iter().collect::<Vec<_>>().into_iter().rev());
+                new_groups
+            };
+            variant_groups = reinsert(variant_groups);
+            indel_groups = reinsert(indel_groups);
+        }
+        let calls = analyse_variant_groups(
+            variant_groups,
+            indel_groups,
+            &kmer_samples,
+            arguments,
+            &data_info,
+        )
+        .unwrap();
+
+        let mut snps: Vec<_> = calls.snps.iter().collect();
+        snps.sort_by_key(|(key, _)| **key);
+        snps.iter()
+            .map(|(key, snp)| format!("SNP {:?} {}", key, snp.column.iter().collect::<String>()))
+            .chain(calls.indels.iter().map(|indel| {
+                format!(
+                    "indel {} {} {} {} {:?}",
+                    indel.contig,
+                    indel.position,
+                    indel.ref_allele,
+                    indel.alt_allele,
+                    indel.genotypes
+                )
+            }))
+            .chain(
+                calls
+                    .unpositioned_snps
+                    .iter()
+                    .map(|snp| format!("{:?}", snp)),
+            )
+            .chain(
+                calls
+                    .unpositioned_indels
+                    .iter()
+                    .map(|indel| format!("{:?}", indel)),
+            )
+            .collect()
+    }
+
+    #[test]
+    fn deterministic_variant_calls() {
+        let genomes = sample_genomes();
+        let reference =
+            std::env::temp_dir().join(format!("skalo_test_reference_{}.fa", std::process::id()));
+        std::fs::write(
+            &reference,
+            format!(">chr\n{}\n", String::from_utf8(genomes[0].clone()).unwrap()),
+        )
+        .unwrap();
+
+        // without reference genome (SNPs numbered in the order of processing), and with a reference genome
+        for reference_genome in [None, Some(reference.clone())] {
+            let config = |nb_threads: usize| Config {
+                nb_threads,
+                reference_genome: reference_genome.clone(),
+                ..Default::default()
+            };
+            let calls = variant_calls(&genomes, &config(1), false);
+            assert_eq!(
+                calls.iter().filter(|call| call.starts_with("SNP")).count(),
+                5
+            );
+            // 5 SNPs and the deletion
+            assert_eq!(calls.len(), 6);
+            assert_eq!(variant_calls(&genomes, &config(4), false), calls);
+            assert_eq!(variant_calls(&genomes, &config(4), true), calls);
+            assert_eq!(variant_calls(&genomes, &config(1), true), calls);
+        }
+        std::fs::remove_file(&reference).unwrap();
+    }
+
+    #[test]
+    fn trim_alleles_left_aligned() {
+        // deletion and insertion in a homopolymer are anchored on the base before the repeat
+        assert_eq!(
+            trim_alleles("CATTTG", "CATTG"),
+            (1, "AT".to_string(), "A".to_string())
+        );
+        assert_eq!(
+            trim_alleles("GAAC", "GAAAC"),
+            (0, "G".to_string(), "GA".to_string())
+        );
+        // substitution followed by an insertion
+        assert_eq!(
+            trim_alleles("CGTGAC", "CATGGTAC"),
+            (0, "CGTG".to_string(), "CATGGT".to_string())
+        );
+        assert_eq!(
+            trim_alleles("A", "T"),
+            (0, "A".to_string(), "T".to_string())
+        );
+    }
+
+    fn snp(column: &str) -> SnpInfo {
+        SnpInfo {
+            column: column.chars().collect(),
+            annotation: VariantAnnotation::default(),
+        }
+    }
+
+    #[test]
+    fn merge_snps_fills_missing_data() {
+        let mut saved = snp("A-C-");
+        assert!(merge_snps(&mut saved, &['A', 'G', '-', '-'], false, 0.5));
+        assert_eq!(saved.column, vec!['A', 'G', 'C', '-']);
+        assert_eq!(
+            (saved.annotation.nb_merged, saved.annotation.conflict),
+            (1, false)
+        );
+        assert_eq!(saved.annotation.ratio_missing, 0.25);
+        // an 'N' (both nucleotides) is kept
+        let mut saved = snp("ACGN");
+        assert!(merge_snps(&mut saved, &['A', 'C', 'G', 'T'], false, 0.5));
+        assert_eq!(saved.column, vec!['A', 'C', 'G', 'N']);
+    }
+
+    #[test]
+    fn merge_snps_conflicting_alleles() {
+        // the saved column is kept and flagged when a sample has different nucleotides
+        let mut saved = snp("ACG-");
+        assert!(!merge_snps(&mut saved, &['A', 'T', 'G', 'C'], false, 0.5));
+        assert_eq!(saved.column, vec!['A', 'C', 'G', '-']);
+        assert_eq!(
+            (saved.annotation.nb_merged, saved.annotation.conflict),
+            (0, true)
+        );
+
+        // the saved column is kept when the merged column does not pass the filters
+        let mut saved = snp("A---");
+        assert!(!merge_snps(&mut saved, &['-', 'A', '-', '-'], false, 0.5));
+        assert_eq!(saved.column, vec!['A', '-', '-', '-']);
+        assert!(!saved.annotation.conflict);
+    }
+
+    #[test]
+    fn merge_snps_mixed_calls() {
+        // different nucleotides become the IUPAC code of their union
+        let mut saved = snp("ACR-");
+        assert!(merge_snps(&mut saved, &['G', 'C', 'T', 'T'], true, 0.0));
+        assert_eq!(saved.column, vec!['R', 'C', 'D', 'T']);
+        assert!(!saved.annotation.conflict);
+        assert_eq!(saved.annotation.ratio_missing, 0.0);
+        assert_eq!(check_missing_data(4, &saved.column), (true, 0.0));
+    }
+}
@@ -0,0 +1,162 @@
+use hashbrown::HashMap;
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::utils::SnpInfo;
+
+// quality metrics of a sample, to flag problematic samples (e.g., contaminated or low-coverage isolates)
+#[derive(Debug, Clone, Serialize)]
+pub struct SampleQc {
+    pub sample: String,
+    // SNP positions with a nucleotide, with missing data ('-'), with ambiguous data ('N') and with a mixed call
+    // (IUPAC code, with mixed calls)
+    pub snps_called: usize,
+    pub snps_missing: usize,
+    pub snps_ambiguous: usize,
+    pub snps_mixed: usize,
+    // SNPs where the sample is the only one with its nucleotide
+    pub private_snps: usize,
+    // fraction of the k-mers of the graph present in the sample
+    pub kmer_fraction: f64,
+}
+
+// compute the quality metrics of each sample from the final SNP columns and its number of k-mers
+pub fn sample_qc(
+    snps: &HashMap<(usize, u32), SnpInfo>,
+    sample_names: &[String],
+    kmers_per_sample: &[usize],
+    nb_kmers: usize,
+) -> Vec<SampleQc> {
+    let mut qc: Vec<SampleQc> = sample_names
+        .iter()
+        .zip(kmers_per_sample)
+        .map(|(name, &nb_sample_kmers)| SampleQc {
+            sample: name.clone(),
+            snps_called: 0,
+            snps_missing: 0,
+            snps_ambiguous: 0,
+            snps_mixed: 0,
+            private_snps: 0,
+            kmer_fraction: if nb_kmers > 0 {
+                nb_sample_kmers as f64 / nb_kmers as f64
+            } else {
+                0.0
+            },
+        })
+        .collect();
+
+    for snp in snps.values() {
+        let mut nucl_counts: HashMap<char, usize> = HashMap::with_capacity(4);
+        for &nucl in &snp.column {
+            if matches!(nucl, 'A' | 'C' | 'G' | 'T') {
+                *nucl_counts.entry(nucl).or_insert(0) += 1;
+            }
+        }
+        for (sample_qc, &nucl) in qc.iter_mut().zip(&snp.column) {
+            match nucl {
+                'A' | 'C' | 'G' | 'T' => {
+                    sample_qc.snps_called += 1;
+                    if nucl_counts[&nucl] == 1 {
+                        sample_qc.private_snps += 1;
+                    }
+                }
+                '-' => sample_qc.snps_missing += 1,
+                'N' => sample_qc.snps_ambiguous += 1,
+                _ => sample_qc.snps_mixed += 1,
+            }
+        }
+    }
+    qc
+}
+
+// write the quality metrics of each sample in TSV format
+pub fn write_sample_qc_tsv(output: &mut impl Write, qc: &[SampleQc]) -> io::Result<()> {
+    writeln!(
+        output,
+        "sample\tsnps_called\tsnps_missing\tsnps_ambiguous\tsnps_mixed\tprivate_snps\tkmer_fraction"
+    )?;
+    for sample_qc in qc {
+        writeln!(
+            output,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{:.4}",
+            sample_qc.sample,
+            sample_qc.snps_called,
+            sample_qc.snps_missing,
+            sample_qc.snps_ambiguous,
+            sample_qc.snps_mixed,
+            sample_qc.private_snps,
+            sample_qc.kmer_fraction
+        )?;
+    }
+    Ok(())
+}
+
+// write the quality metrics of each sample in JSON format (one object per sample)
+pub fn write_sample_qc_json(output: &mut impl Write, qc: &[SampleQc]) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *output, qc)?;
+    writeln!(output)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::VariantAnnotation;
+
+    #[test]
+    fn sample_qc_counts() {
+        let snps: HashMap<(usize, u32), SnpInfo> = ["AACG", "CC-T", "GNRG"]
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                (
+                    (0, i as u32),
+                    SnpInfo {
+                        column: column.chars().collect(),
+                        annotation: VariantAnnotation::default(),
+                    },
+                )
+            })
+            .collect();
+        let names: Vec<String> = ["s1", "s2", "s3", "s4"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        let qc = sample_qc(&snps, &names, &[80, 100, 50, 0], 100);
+
+        let counts: Vec<(usize, usize, usize, usize, usize)> = qc
+            .iter()
+            .map(|sample_qc| {
+                (
+                    sample_qc.snps_called,
+                    sample_qc.snps_missing,
+                    sample_qc.snps_ambiguous,
+                    sample_qc.snps_mixed,
+                    sample_qc.private_snps,
+                )
+            })
+            .collect();
+        // s1: A (shared), C (shared), G (shared); s2: A, C, N; s3: C (private), -, R; s4: G (private), T (private), G
+        assert_eq!(
+            counts,
+            vec![
+                (3, 0, 0, 0, 0),
+                (2, 0, 1, 0, 0),
+                (1, 1, 0, 1, 1),
+                (3, 0, 0, 0, 2)
+            ]
+        );
+        assert_eq!(qc[0].sample, "s1");
+        assert_eq!(
+            qc.iter()
+                .map(|sample_qc| sample_qc.kmer_fraction)
+                .collect::<Vec<f64>>(),
+            vec![0.8, 1.0, 0.5, 0.0]
+        );
+        assert_eq!(
+            sample_qc(&snps, &names, &[0, 0, 0, 0], 0)[1].kmer_fraction,
+            0.0
+        );
+    }
+}
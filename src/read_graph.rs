@@ -1,72 +1,91 @@
 use hashbrown::{HashMap, HashSet};
 //use std::time::Instant;
-use bit_set::BitSet;
 
+use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
 use rayon::ThreadPoolBuilder;
-use std::sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}};
-use indicatif::{ProgressBar, ProgressStyle};
+use std::cmp::Reverse;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
 
-use crate::utils::{decode_kmer, get_last_nucl, VariantInfo, DnaSequence, DATA_INFO, CONFIG};
 use crate::compaction::compact_graph;
-use crate::process_variants::analyse_variant_groups;
+use crate::error::SkaloError;
+use crate::graph::KmerGraph;
+use crate::utils::{
+    decode_kmer, get_last_nucl, progress, Config, DataInfo, DnaSequence, KmerInt, VariantGroups,
+    VariantInfo,
+};
+
+// returns the variant groups and the indel groups
+pub fn build_variant_groups<IntT: KmerInt>(
+    mut all_kmers: KmerGraph<IntT>,
+    start_kmers: &HashSet<IntT>,
+    end_kmers: &HashSet<IntT>,
+    arguments: &Config,
+    data_info: &DataInfo,
+) -> Result<(VariantGroups<IntT>, VariantGroups<IntT>), SkaloError> {
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(arguments.nb_threads)
+        .build()
+        .map_err(|e| SkaloError::ThreadPool(e.to_string()))?;
 
+    progress!(arguments.verbose, " # compact graph");
 
-pub fn build_variant_groups(mut all_kmers: HashMap<u128, Vec<u128>>, start_kmers: HashSet<u128>, end_kmers: HashSet<u128>, kmer_2_samples: HashMap<u128, BitSet>) {
-    
-    
-    let arguments = CONFIG.get().unwrap();
-    let data_info = DATA_INFO.get().unwrap();
-    
-    println!(" # compact graph");
-        
-    let compacted = compact_graph(&mut all_kmers, &start_kmers, &end_kmers);
+    let compacted = pool.install(|| compact_graph(&mut all_kmers, start_kmers, end_kmers));
+
+    progress!(arguments.verbose, " # explore graph");
 
-    println!(" # explore graph");
-    
     //let start = Instant::now();
-    
-    let built_groups = Arc::new(Mutex::new(HashMap::<(u128, u128), Vec<VariantInfo>>::new()));
-    
-    let pool = ThreadPoolBuilder::new().num_threads(arguments.nb_threads).build().unwrap();
-    
-    let pb = ProgressBar::new(start_kmers.len().try_into().unwrap());
-    let sty = ProgressStyle::with_template("   {bar:60.cyan/blue} {pos:>7}/{len:7} {msg}",).unwrap().progress_chars("##-");
+
+    let built_groups = Arc::new(Mutex::new(VariantGroups::new()));
+
+    let pb = if arguments.verbose {
+        ProgressBar::new(start_kmers.len() as u64)
+    } else {
+        ProgressBar::hidden()
+    };
+    let sty = ProgressStyle::with_template("   {bar:60.cyan/blue} {pos:>7}/{len:7} {msg}")
+        .unwrap()
+        .progress_chars("##-");
     pb.set_style(sty);
     let counter_pb = AtomicUsize::new(0);
-        
+
     pool.install(|| {
-        start_kmers.par_iter().for_each(|kmer| {
-            if counter_pb.fetch_add(1, Ordering::SeqCst) % 1000 == 0 {
+        start_kmers.par_iter().try_for_each(|kmer| {
+            if counter_pb
+                .fetch_add(1, Ordering::SeqCst)
+                .is_multiple_of(1000)
+            {
                 pb.inc(1000);
             }
-            
-            let mut tmp_container: HashMap<u128, Vec<Vec<u128>>> = HashMap::new();
 
-            let mut good_next: Vec<u128> = Vec::with_capacity(2);
-            
-            for starting_kmer in all_kmers.get(kmer).unwrap().iter() {
-                
+            let mut tmp_container: HashMap<IntT, Vec<Vec<IntT>>> = HashMap::new();
+
+            let mut good_next: Vec<IntT> = Vec::with_capacity(2);
+
+            for starting_kmer in all_kmers.next_kmers(*kmer) {
                 let mut visited = HashSet::new();
                 visited.insert(*kmer);
-                visited.insert(*starting_kmer);
-                
-                let mut vec_visited = vec![*kmer, *starting_kmer];
-                
+                visited.insert(starting_kmer);
+
+                let mut vec_visited = vec![*kmer, starting_kmer];
+
                 // add compacted nodes
-                if compacted.contains_key(starting_kmer) {
-                    let vec_compacted = compacted.get(starting_kmer).unwrap();
+                if compacted.contains_key(&starting_kmer) {
+                    let vec_compacted = compacted.get(&starting_kmer).unwrap();
                     vec_visited.extend(vec_compacted.iter());
                 }
-                
+
                 // Initialize the stack with the starting kmer
                 let mut stack = vec![PathState {
-                    current_kmer: *starting_kmer,
+                    current_kmer: starting_kmer,
                     visited,
                     vec_visited,
                     depth: 0,
                 }];
-                
+
                 // Process each path in the stack
                 while let Some(path_state) = stack.pop() {
                     let PathState {
@@ -81,18 +100,16 @@ pub fn build_variant_groups(mut all_kmers: HashMap<u128, Vec<u128>>, start_kmers
                     }
 
                     let mut walking_along_path = true;
-                    
+
                     while walking_along_path {
                         good_next.truncate(0);
-                        if let Some(next_kmer_data) = all_kmers.get(&current_kmer) {
-                            // add next kmers that have not yet been visited 
-                            for &kmer2 in next_kmer_data {
-                                if !visited.contains(&kmer2) {
-                                    good_next.push(kmer2);
-                                }
-                            }                        
+                        // add next kmers that have not yet been visited
+                        for kmer2 in all_kmers.next_kmers(current_kmer) {
+                            if !visited.contains(&kmer2) {
+                                good_next.push(kmer2);
+                            }
                         }
- 
+
                         match good_next.len() {
                             1 => {
                                 // single path continuation
@@ -100,16 +117,19 @@ pub fn build_variant_groups(mut all_kmers: HashMap<u128, Vec<u128>>, start_kmers
                                 visited.insert(next);
                                 vec_visited.push(next);
                                 current_kmer = next;
-                                
+
                                 // add compacted nodes
                                 if compacted.contains_key(&next) {
                                     let vec_compacted = compacted.get(&next).unwrap();
                                     vec_visited.extend(vec_compacted.iter());
                                 }
-                                
+
                                 if end_kmers.contains(&next) {
                                     // save possible variant
-                                    tmp_container.entry(next).or_default().push(vec_visited.clone());
+                                    tmp_container
+                                        .entry(next)
+                                        .or_default()
+                                        .push(vec_visited.clone());
                                 }
                             }
                             len if len > 1 => {
@@ -117,21 +137,24 @@ pub fn build_variant_groups(mut all_kmers: HashMap<u128, Vec<u128>>, start_kmers
                                 for next in &good_next {
                                     let mut new_visited = visited.clone();
                                     new_visited.insert(*next);
-                        
+
                                     let mut new_vec_visited = vec_visited.clone();
                                     new_vec_visited.push(*next);
-                                    
+
                                     // add compacted nodes
                                     if compacted.contains_key(next) {
                                         let vec_compacted = compacted.get(next).unwrap();
                                         new_vec_visited.extend(vec_compacted.iter());
                                     }
-                                    
+
                                     // save possible variant
                                     if end_kmers.contains(next) {
-                                        tmp_container.entry(*next).or_insert_with(Vec::new).push(new_vec_visited.clone());
+                                        tmp_container
+                                            .entry(*next)
+                                            .or_insert_with(Vec::new)
+                                            .push(new_vec_visited.clone());
                                     }
-                                    
+
                                     // initiate new path
                                     if walking_along_path {
                                         stack.push(PathState {
@@ -139,7 +162,7 @@ pub fn build_variant_groups(mut all_kmers: HashMap<u128, Vec<u128>>, start_kmers
                                             visited: new_visited,
                                             vec_visited: new_vec_visited,
                                             depth: depth + 1,
-                                        });                        
+                                        });
                                     }
                                 }
                                 // stop current path exploration after branching
@@ -149,24 +172,23 @@ pub fn build_variant_groups(mut all_kmers: HashMap<u128, Vec<u128>>, start_kmers
                                 // no further paths
                                 walking_along_path = false;
                             }
-                        }                                                
+                        }
                     }
                 }
             }
-            
+
             // save variants if at least a vector with 2+ elements for one exit k-mer
             if tmp_container.values().any(|v| v.len() > 1) {
-                
                 // prepare variant container
-                let mut tmp_container_2: HashMap<(u128,u128), Vec<VariantInfo>> = HashMap::new();
-                
+                let mut tmp_container_2: HashMap<(IntT, IntT), Vec<VariantInfo>> = HashMap::new();
+
                 // check-filter-build variant groups
                 for (exit_kmer, vec_variants) in tmp_container.iter() {
-                    
                     // collect second to last kmer of each variant in a hashset -> test if at least 2 (ie, the variants end on a difference)
-                    let second_set: HashSet<u128> = vec_variants.iter().map(|v| v[1]).collect();
-                    let second_to_last_set: HashSet<u128> = vec_variants.iter().map(|v| v[v.len() - 2]).collect();
-                    
+                    let second_set: HashSet<IntT> = vec_variants.iter().map(|v| v[1]).collect();
+                    let second_to_last_set: HashSet<IntT> =
+                        vec_variants.iter().map(|v| v[v.len() - 2]).collect();
+
                     if second_set.len() > 1 && second_to_last_set.len() > 1 {
                         if let Some(most_common_length) = most_abundant_length(vec_variants) {
                             let filtered_variants: Vec<_> = if vec_variants.len() == 2 {
@@ -178,68 +200,82 @@ pub fn build_variant_groups(mut all_kmers: HashMap<u128, Vec<u128>>, start_kmers
                                     .cloned()
                                     .collect()
                             };
-                            
-                            let combined_ends: (u128, u128) = (*kmer, *exit_kmer);
+
+                            let combined_ends: (IntT, IntT) = (*kmer, *exit_kmer);
 
                             // build variants 1 by 1
                             for vec_visited in filtered_variants {
-                                
                                 // build sequence
-                                let mut sequence = String::with_capacity(vec_visited.len() + data_info.k_graph -1);
+                                let mut sequence = String::with_capacity(
+                                    vec_visited.len() + data_info.k_graph - 1,
+                                );
                                 sequence.push_str(&decode_kmer(*kmer, data_info.k_graph));
-                                let mut vec_snps: Vec<usize> = Vec::new();  
+                                let mut vec_snps: Vec<usize> = Vec::new();
                                 for (i, next) in vec_visited.iter().enumerate() {
-                                    if i!= 0 {  // 1st corresponds to entry k-mer
+                                    if i != 0 {
+                                        // 1st corresponds to entry k-mer
                                         sequence.push(get_last_nucl(*next));
                                     }
-                                    if start_kmers.contains(next) && i <= (vec_visited.len() - data_info.k_graph) {
+                                    // (no bound for paths shorter than k-1 nodes, shortened by the compaction)
+                                    if start_kmers.contains(next)
+                                        && vec_visited
+                                            .len()
+                                            .checked_sub(data_info.k_graph)
+                                            .is_none_or(|last| i <= last)
+                                    {
                                         vec_snps.push(i + data_info.k_graph);
                                     } else if end_kmers.contains(next) {
-                                        vec_snps.push(i -1);
+                                        vec_snps.push(i - 1);
                                     }
-                                } 
-                                
+                                }
+
                                 // save variant to container
-                                let variant = VariantInfo::new(
-                                    DnaSequence::encode(&sequence),
-                                    vec_snps,
-                                );  
+                                let variant =
+                                    VariantInfo::new(DnaSequence::encode(&sequence)?, vec_snps);
 
-                                tmp_container_2.entry(combined_ends)
+                                tmp_container_2
+                                    .entry(combined_ends)
                                     .or_default()
                                     .push(variant);
-                            }                        
+                            }
                         }
                     }
-                }  
-                // save it to main variable       
+                }
+                // save it to main variable
                 if !tmp_container_2.is_empty() {
                     let mut built_groups_locked = built_groups.lock().unwrap();
                     built_groups_locked.extend(tmp_container_2);
-                }    
+                }
             }
-        });
-    });
-    
+            Ok(())
+        })
+    })?;
+
     //let duration = start.elapsed();
     //println!("time taken: {:?}", duration);
-    
+
     let built_groups_end = built_groups.lock().unwrap();
-    
-    println!("     . {} variant groups", built_groups_end.len());    
-    
+
+    progress!(
+        arguments.verbose,
+        "     . {} variant groups",
+        built_groups_end.len()
+    );
+
     // at least one of the 2 branches of an indel should have a size below or equal to this (indel and other >= (1 + 2 * data_info.k_graph))
     let min_indel = 2 * data_info.k_graph;
-    
+
     // separate indels from the other variants
-    let mut final_groups: HashMap<(u128,u128), Vec<VariantInfo>> = HashMap::new();
-    let mut final_indels: HashMap<(u128,u128), Vec<VariantInfo>> = HashMap::new();
-    
+    let mut final_groups: VariantGroups<IntT> = HashMap::new();
+    let mut final_indels: VariantGroups<IntT> = HashMap::new();
+
     for (extremities_combined, vec_variant) in built_groups_end.iter() {
         // test if variant is an indel
         if vec_variant.len() < 2 {
             continue;
-        } else if vec_variant.len() == 2 && vec_variant[0].sequence.len() != vec_variant[1].sequence.len() {
+        } else if vec_variant.len() == 2
+            && vec_variant[0].sequence.len() != vec_variant[1].sequence.len()
+        {
             let mut is_indel = false;
             for variant in vec_variant {
                 if variant.sequence.len() <= min_indel {
@@ -248,20 +284,20 @@ pub fn build_variant_groups(mut all_kmers: HashMap<u128, Vec<u128>>, start_kmers
             }
             if is_indel {
                 final_indels.insert(*extremities_combined, vec_variant.clone());
+            } else if arguments.complex_variants {
+                // substitution + indel: only reported as a complex variant
+                final_groups.insert(*extremities_combined, vec_variant.clone());
             }
         } else {
             final_groups.insert(*extremities_combined, vec_variant.clone());
         }
     }
-    
-    // infer variants
-    analyse_variant_groups(final_groups, final_indels, kmer_2_samples);
-}
-
 
+    Ok((final_groups, final_indels))
+}
 
 // find the most abundant length in a vector of variants
-fn most_abundant_length(vec_variants: &[Vec<u128>]) -> Option<usize> {
+fn most_abundant_length<IntT>(vec_variants: &[Vec<IntT>]) -> Option<usize> {
     let mut length_counts = std::collections::HashMap::new();
 
     // count the frequency of each length
@@ -269,17 +305,17 @@ fn most_abundant_length(vec_variants: &[Vec<u128>]) -> Option<usize> {
         *length_counts.entry(variant.len()).or_insert(0) += 1;
     }
 
-    // find the length with the maximum count
-    length_counts.into_iter().max_by_key(|&(_, count)| count).map(|(length, _)| length)
+    // find the length with the maximum count (the shortest one in case of tie)
+    length_counts
+        .into_iter()
+        .max_by_key(|&(length, count)| (count, Reverse(length)))
+        .map(|(length, _)| length)
 }
 
-
-
 // structure to hold state for each path in the stack
-pub struct PathState {
-    current_kmer: u128,
-    visited: HashSet<u128>,
-    vec_visited: Vec<u128>,
+pub struct PathState<IntT> {
+    current_kmer: IntT,
+    visited: HashSet<IntT>,
+    vec_visited: Vec<IntT>,
     depth: usize,
 }
-
@@ -0,0 +1,445 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use hashbrown::{HashMap, HashSet};
+use regex::Regex;
+
+use crate::error::SkaloError;
+use crate::utils::{progress, DataInfo};
+
+// sample sheet: display name and metadata values of each sample, indexed by its original name
+pub struct SampleSheet {
+    // names of the metadata columns
+    pub columns: Vec<String>,
+    samples: HashMap<String, (String, Vec<String>)>,
+}
+
+impl SampleSheet {
+    // display name and metadata of the samples (original name and empty metadata for samples absent from the sheet)
+    pub fn apply(&self, original_names: &[String]) -> (Vec<String>, Vec<Vec<String>>) {
+        original_names
+            .iter()
+            .map(|name| match self.samples.get(name) {
+                Some((display_name, values)) => (display_name.clone(), values.clone()),
+                None => (name.clone(), vec![String::new(); self.columns.len()]),
+            })
+            .unzip()
+    }
+
+    pub fn contains(&self, original_name: &str) -> bool {
+        self.samples.contains_key(original_name)
+    }
+}
+
+// read a TSV sample sheet: header line, then one line per sample with its original name (in the skf file(s) or
+// list of sequence files), its display name (original name if empty) and any number of metadata columns
+pub fn read_sample_sheet(file_path: &Path) -> Result<SampleSheet, SkaloError> {
+    let error = |msg: String| SkaloError::Samples(format!("{}: {}", file_path.display(), msg));
+    let content = fs::read_to_string(file_path).map_err(|e| error(e.to_string()))?;
+
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let header: Vec<&str> = match lines.next() {
+        Some((_, line)) => line
+            .trim_start_matches('#')
+            .split('\t')
+            .map(|field| field.trim())
+            .collect(),
+        None => return Err(error("empty sample sheet".to_string())),
+    };
+    if header.len() < 2 {
+        return Err(error(
+            "the header should have at least 2 columns (original and display names)".to_string(),
+        ));
+    }
+    // metadata columns are keys of the VCF header
+    if let Some(column) = header[2..]
+        .iter()
+        .find(|column| !is_valid_column_name(column))
+    {
+        return Err(error(format!(
+            "invalid column name '{}' (letters, digits, '_' and '.' only, not starting with a digit, and not ID or OriginalName)",
+            column
+        )));
+    }
+
+    let mut samples: HashMap<String, (String, Vec<String>)> = HashMap::new();
+    for (i, line) in lines {
+        let fields: Vec<&str> = line.split('\t').map(|field| field.trim()).collect();
+        if fields.len() != header.len() {
+            return Err(error(format!(
+                "line {} has {} columns instead of {}",
+                i + 1,
+                fields.len(),
+                header.len()
+            )));
+        }
+        let display_name = if fields[1].is_empty() {
+            fields[0]
+        } else {
+            fields[1]
+        };
+        if !is_valid_sample_name(display_name) {
+            return Err(error(format!(
+                "invalid sample name '{}' on line {} (no whitespace, ',', '=', ';' or '>')",
+                display_name,
+                i + 1
+            )));
+        }
+        let values = fields[2..].iter().map(|value| value.to_string()).collect();
+        if samples
+            .insert(fields[0].to_string(), (display_name.to_string(), values))
+            .is_some()
+        {
+            return Err(error(format!("sample {} listed more than once", fields[0])));
+        }
+    }
+
+    Ok(SampleSheet {
+        columns: header[2..]
+            .iter()
+            .map(|column| column.to_string())
+            .collect(),
+        samples,
+    })
+}
+
+// display names are written in FASTA headers and VCF header lines
+fn is_valid_sample_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, ',' | '=' | ';' | '>'))
+}
+
+// metadata columns are written as keys of the VCF header lines (ID and OriginalName are already used)
+fn is_valid_column_name(column: &str) -> bool {
+    let mut chars = column.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        && column != "ID"
+        && column != "OriginalName"
+}
+
+// write the original name and metadata of each sample in TSV format
+pub fn write_sample_report(output: &mut impl Write, data_info: &DataInfo) -> io::Result<()> {
+    write!(output, "sample\toriginal_name")?;
+    for column in &data_info.metadata_columns {
+        write!(output, "\t{}", column)?;
+    }
+    writeln!(output)?;
+    for (i, name) in data_info.sample_names.iter().enumerate() {
+        write!(output, "{}\t{}", name, data_info.original_names[i])?;
+        for value in &data_info.sample_metadata[i] {
+            write!(output, "\t{}", value)?;
+        }
+        writeln!(output)?;
+    }
+    Ok(())
+}
+
+// metadata of a sample as 'column=value' fields, for FASTA headers (whitespace in values replaced by '_')
+pub fn metadata_description(data_info: &DataInfo, sample: usize) -> String {
+    data_info
+        .metadata_columns
+        .iter()
+        .zip(&data_info.sample_metadata[sample])
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(column, value)| {
+            format!(
+                " {}={}",
+                column,
+                value.split_whitespace().collect::<Vec<_>>().join("_")
+            )
+        })
+        .collect()
+}
+
+// samples selected by a file listing their names (one per line) or by a regular expression given with the
+// 'regex:' prefix and matched against the whole sample names
+enum SampleSelection {
+    Names(HashSet<String>),
+    Pattern(Regex),
+}
+
+impl SampleSelection {
+    fn parse(selection: &str, option: &str) -> Result<Self, SkaloError> {
+        if let Some(pattern) = selection.strip_prefix("regex:") {
+            let error = |e: regex::Error| {
+                SkaloError::Samples(format!("{}: invalid regular expression: {}", option, e))
+            };
+            Regex::new(pattern).map_err(error)?;
+            Ok(SampleSelection::Pattern(
+                Regex::new(&format!("^(?:{})$", pattern)).map_err(error)?,
+            ))
+        } else {
+            let content = fs::read_to_string(selection)
+                .map_err(|e| SkaloError::Samples(format!("{}: {}: {}", option, selection, e)))?;
+            Ok(SampleSelection::Names(
+                content
+                    .lines()
+                    .map(|line| line.trim())
+                    .filter(|line| !line.is_empty())
+                    .map(|line| line.to_string())
+                    .collect(),
+            ))
+        }
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        match self {
+            SampleSelection::Names(names) => names.contains(name),
+            SampleSelection::Pattern(pattern) => pattern.is_match(name),
+        }
+    }
+
+    // names of the list absent from the samples
+    fn nb_unknown_names(&self, original_names: &[String], display_names: &[String]) -> usize {
+        match self {
+            SampleSelection::Names(names) => names
+                .iter()
+                .filter(|name| !original_names.contains(name) && !display_names.contains(name))
+                .count(),
+            SampleSelection::Pattern(_) => 0,
+        }
+    }
+}
+
+// new index of each sample after the inclusion (if any) and exclusion of samples (None if the sample is excluded);
+// samples are selected by their original or display name
+pub fn select_samples(
+    original_names: &[String],
+    display_names: &[String],
+    include: Option<&str>,
+    exclude: Option<&str>,
+    verbose: bool,
+) -> Result<Vec<Option<usize>>, SkaloError> {
+    let include = include
+        .map(|selection| SampleSelection::parse(selection, "--include-samples"))
+        .transpose()?;
+    let exclude = exclude
+        .map(|selection| SampleSelection::parse(selection, "--exclude-samples"))
+        .transpose()?;
+
+    for selection in include.iter().chain(exclude.iter()) {
+        let nb_unknown = selection.nb_unknown_names(original_names, display_names);
+        if nb_unknown > 0 {
+            progress!(
+                verbose,
+                "     . {} listed sample(s) not found in the input",
+                nb_unknown
+            );
+        }
+    }
+
+    let mut nb_selected = 0;
+    let new_indexes: Vec<Option<usize>> = original_names
+        .iter()
+        .zip(display_names)
+        .map(|(original_name, display_name)| {
+            let is_in = |selection: &SampleSelection| {
+                selection.contains(original_name) || selection.contains(display_name)
+            };
+            let selected =
+                include.as_ref().is_none_or(is_in) && !exclude.as_ref().is_some_and(is_in);
+            selected.then(|| {
+                nb_selected += 1;
+                nb_selected - 1
+            })
+        })
+        .collect();
+
+    if nb_selected == 0 {
+        return Err(SkaloError::Samples(
+            "no sample left after the inclusion/exclusion of samples".to_string(),
+        ));
+    }
+    Ok(new_indexes)
+}
+
+// values of the selected samples (see select_samples())
+pub fn keep_selected<T>(values: Vec<T>, new_indexes: &[Option<usize>]) -> Vec<T> {
+    values
+        .into_iter()
+        .zip(new_indexes)
+        .filter(|(_, index)| index.is_some())
+        .map(|(value, _)| value)
+        .collect()
+}
+
+// display names have to be unique (after renaming and selection of the samples)
+pub fn check_unique_names(sample_names: &[String]) -> Result<(), SkaloError> {
+    let mut unique_names: HashSet<&str> = HashSet::with_capacity(sample_names.len());
+    match sample_names
+        .iter()
+        .find(|name| !unique_names.insert(name.as_str()))
+    {
+        Some(name) => Err(SkaloError::Samples(format!(
+            "several samples named {}",
+            name
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn select_samples_by_regex() {
+        let original_names = names(&["s1", "s2", "out_1", "out_2"]);
+        let display_names = names(&["a", "b", "c", "d"]);
+
+        let selected = select_samples(
+            &original_names,
+            &display_names,
+            None,
+            Some("regex:out_.*"),
+            false,
+        )
+        .unwrap();
+        assert_eq!(selected, vec![Some(0), Some(1), None, None]);
+        // patterns are matched against the whole names
+        let selected = select_samples(
+            &original_names,
+            &display_names,
+            None,
+            Some("regex:out"),
+            false,
+        )
+        .unwrap();
+        assert_eq!(selected, vec![Some(0), Some(1), Some(2), Some(3)]);
+        // original or display name
+        let selected = select_samples(
+            &original_names,
+            &display_names,
+            Some("regex:s1|c"),
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(selected, vec![Some(0), None, Some(1), None]);
+
+        assert!(matches!(
+            select_samples(
+                &original_names,
+                &display_names,
+                Some("regex:("),
+                None,
+                false
+            ),
+            Err(SkaloError::Samples(_))
+        ));
+        assert!(matches!(
+            select_samples(
+                &original_names,
+                &display_names,
+                None,
+                Some("regex:.*"),
+                false
+            ),
+            Err(SkaloError::Samples(_))
+        ));
+    }
+
+    #[test]
+    fn select_samples_by_list() {
+        let original_names = names(&["s1", "s2", "out_1", "out_2"]);
+        let display_names = names(&["a", "b", "c", "d"]);
+
+        let list_file =
+            std::env::temp_dir().join(format!("skalo_test_samples_{}.txt", std::process::id()));
+        fs::write(&list_file, "a\nout_2\n\nunknown\n").unwrap();
+        let list = list_file.to_str().unwrap();
+        let included = select_samples(&original_names, &display_names, Some(list), None, false);
+        let excluded = select_samples(
+            &original_names,
+            &display_names,
+            Some("regex:.*"),
+            Some(list),
+            false,
+        );
+        fs::remove_file(&list_file).unwrap();
+
+        assert_eq!(included.unwrap(), vec![Some(0), None, None, Some(1)]);
+        assert_eq!(excluded.unwrap(), vec![None, Some(0), Some(1), None]);
+        // missing list file
+        assert!(matches!(
+            select_samples(&original_names, &display_names, Some(list), None, false),
+            Err(SkaloError::Samples(_))
+        ));
+    }
+
+    fn sample_sheet(name: &str, content: &str) -> Result<SampleSheet, SkaloError> {
+        let sheet_file = std::env::temp_dir().join(format!(
+            "skalo_test_sheet_{}_{}.tsv",
+            name,
+            std::process::id()
+        ));
+        fs::write(&sheet_file, content).unwrap();
+        let sheet = read_sample_sheet(&sheet_file);
+        fs::remove_file(&sheet_file).unwrap();
+        sheet
+    }
+
+    #[test]
+    fn read_valid_sample_sheet() {
+        let sheet = sample_sheet(
+            "valid",
+            "#sample\tname\thost\tyear\n\ns1\tA-1\thuman\t2020\ns2\t\tcow\t\n",
+        )
+        .unwrap();
+        assert_eq!(sheet.columns, names(&["host", "year"]));
+        assert!(sheet.contains("s1") && !sheet.contains("A-1"));
+
+        // empty display name -> original name, and original name for samples absent from the sheet
+        let (display_names, metadata) = sheet.apply(&names(&["s2", "s1", "s3"]));
+        assert_eq!(display_names, names(&["s2", "A-1", "s3"]));
+        assert_eq!(
+            metadata,
+            vec![
+                names(&["cow", ""]),
+                names(&["human", "2020"]),
+                names(&["", ""])
+            ]
+        );
+    }
+
+    #[test]
+    fn read_invalid_sample_sheets() {
+        let invalid_sheets = [
+            ("empty", "\n\n"),
+            ("one_column", "sample\ns1\n"),
+            ("column_count", "sample\tname\thost\ns1\tA\thuman\ns2\tB\n"),
+            ("duplicate", "sample\tname\ns1\tA\ns2\tB\ns1\tC\n"),
+            ("display_name", "sample\tname\ns1\tA;1\n"),
+            ("original_name", "sample\tname\ns 1\t\n"),
+            ("column_digit", "sample\tname\t1host\ns1\tA\thuman\n"),
+            ("column_char", "sample\tname\thost-name\ns1\tA\thuman\n"),
+            ("column_id", "sample\tname\tID\ns1\tA\t1\n"),
+            ("column_original", "sample\tname\tOriginalName\ns1\tA\ts1\n"),
+        ];
+        for (name, content) in invalid_sheets {
+            assert!(
+                matches!(sample_sheet(name, content), Err(SkaloError::Samples(_))),
+                "{}",
+                name
+            );
+        }
+        // missing file
+        assert!(matches!(
+            read_sample_sheet(Path::new("skalo_missing_sheet.tsv")),
+            Err(SkaloError::Samples(_))
+        ));
+    }
+}
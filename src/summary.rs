@@ -0,0 +1,77 @@
+use std::io::Write;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::error::SkaloError;
+use crate::output::write_file;
+use crate::utils::Config;
+
+// summary of a run (statistics, parameters, timings and version), written in JSON format
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    pub version: String,
+    pub parameters: Config,
+    pub statistics: RunStatistics,
+    // duration of each stage of the pipeline, in order
+    pub timings: Vec<StageTiming>,
+    // maximum resident set size of the process (None if not available)
+    pub peak_memory_mb: Option<f64>,
+}
+
+// statistics of the graph and of the inferred variants
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunStatistics {
+    pub kmer_length: usize,
+    pub nb_samples: usize,
+    // distinct (k-1)-mers of the graph and distinct sets of samples of the k-mers
+    pub nb_nodes: usize,
+    pub nb_colour_sets: usize,
+    pub nb_entry_nodes: usize,
+    pub nb_variant_groups: usize,
+    pub nb_indel_groups: usize,
+    // SNPs (positioned if reference genome) and SNPs that could not be positioned
+    pub nb_snps: usize,
+    pub nb_unpositioned_snps: usize,
+    // SNPs not saved because of a conflicting SNP at the same position (included in nb_unpositioned_snps)
+    pub nb_snp_collisions: usize,
+    // indels passing the missing data filter, and those that could not be positioned
+    pub nb_indels: usize,
+    pub nb_unpositioned_indels: usize,
+    pub nb_complex_variants: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StageTiming {
+    pub stage: String,
+    pub seconds: f64,
+}
+
+impl RunSummary {
+    pub fn new(arguments: &Config) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            parameters: arguments.clone(),
+            statistics: RunStatistics::default(),
+            timings: Vec::new(),
+            peak_memory_mb: None,
+        }
+    }
+
+    // save the duration of a stage started at 'start'
+    pub fn add_timing(&mut self, stage: &str, start: Instant) {
+        self.timings.push(StageTiming {
+            stage: stage.to_string(),
+            seconds: start.elapsed().as_secs_f64(),
+        });
+    }
+}
+
+// write the summary of the run to '<prefix>_summary.json'
+pub fn write_run_summary(summary: &RunSummary, arguments: &Config) -> Result<(), SkaloError> {
+    let filename = format!("{}_summary.json", arguments.output_name);
+    write_file(&filename, |output| {
+        serde_json::to_writer_pretty(&mut *output, summary)?;
+        writeln!(output)
+    })
+}
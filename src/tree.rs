@@ -0,0 +1,156 @@
+use crate::distances::DistanceMatrix;
+
+// build a neighbour-joining tree from the pairwise SNP distances and return it in Newick format
+// (unrooted tree, with branch lengths in number of SNPs; negative branch lengths are set to 0)
+pub fn neighbour_joining(distances: &DistanceMatrix, sample_names: &[String]) -> String {
+    // subtrees (in Newick format) and distances between them
+    let mut nodes: Vec<String> = sample_names.iter().map(|name| newick_label(name)).collect();
+    let mut matrix: Vec<Vec<f64>> = distances
+        .iter()
+        .map(|row| row.iter().map(|&d| d as f64).collect())
+        .collect();
+
+    match nodes.len() {
+        0 => return ";".to_string(),
+        1 => return format!("({});", nodes[0]),
+        2 => {
+            return format!(
+                "({}:{},{}:{});",
+                nodes[0],
+                branch_length(matrix[0][1] / 2.0),
+                nodes[1],
+                branch_length(matrix[0][1] / 2.0)
+            )
+        }
+        _ => {}
+    }
+
+    while nodes.len() > 3 {
+        let n = nodes.len();
+        let row_sums: Vec<f64> = matrix.iter().map(|row| row.iter().sum()).collect();
+
+        // pair of subtrees minimising the Q criterion (first pair in case of tie)
+        let mut best_pair = (0, 1);
+        let mut best_q = f64::INFINITY;
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let q = (n as f64 - 2.0) * matrix[i][j] - row_sums[i] - row_sums[j];
+                if q < best_q {
+                    best_q = q;
+                    best_pair = (i, j);
+                }
+            }
+        }
+        let (i, j) = best_pair;
+
+        // branch lengths from the new node to the joined subtrees
+        let length_i = matrix[i][j] / 2.0 + (row_sums[i] - row_sums[j]) / (2.0 * (n as f64 - 2.0));
+        let length_j = matrix[i][j] - length_i;
+        let new_node = format!(
+            "({}:{},{}:{})",
+            nodes[i],
+            branch_length(length_i),
+            nodes[j],
+            branch_length(length_j)
+        );
+
+        // distances from the new node to the other subtrees
+        let new_distances: Vec<f64> = (0..n)
+            .filter(|&k| k != i && k != j)
+            .map(|k| (matrix[i][k] + matrix[j][k] - matrix[i][j]) / 2.0)
+            .collect();
+
+        // replace the joined subtrees by the new node (j > i, so remove j first)
+        for index in [j, i] {
+            nodes.remove(index);
+            matrix.remove(index);
+            for row in matrix.iter_mut() {
+                row.remove(index);
+            }
+        }
+        for (row, &distance) in matrix.iter_mut().zip(&new_distances) {
+            row.push(distance);
+        }
+        let mut new_row = new_distances;
+        new_row.push(0.0);
+        matrix.push(new_row);
+        nodes.push(new_node);
+    }
+
+    // join the 3 last subtrees on a central node
+    let length_0 = (matrix[0][1] + matrix[0][2] - matrix[1][2]) / 2.0;
+    let length_1 = matrix[0][1] - length_0;
+    let length_2 = matrix[0][2] - length_0;
+    format!(
+        "({}:{},{}:{},{}:{});",
+        nodes[0],
+        branch_length(length_0),
+        nodes[1],
+        branch_length(length_1),
+        nodes[2],
+        branch_length(length_2)
+    )
+}
+
+fn branch_length(length: f64) -> String {
+    format!("{}", (length.max(0.0) * 1e6).round() / 1e6)
+}
+
+// quote sample names containing characters with a meaning in Newick format
+fn newick_label(name: &str) -> String {
+    if name
+        .chars()
+        .any(|c| "()[]':;,".contains(c) || c.is_whitespace())
+    {
+        format!("'{}'", name.replace('\'', "''"))
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn neighbour_joining_additive_matrix() {
+        // distances of the tree ((A:2,B:3):3,(C:4,D:5)), recovered exactly
+        let distances: DistanceMatrix = vec![
+            vec![0, 5, 9, 10],
+            vec![5, 0, 10, 11],
+            vec![9, 10, 0, 9],
+            vec![10, 11, 9, 0],
+        ];
+        assert_eq!(
+            neighbour_joining(&distances, &names(&["A", "B", "C", "D"])),
+            "(C:4,D:5,(A:2,B:3):3);"
+        );
+    }
+
+    #[test]
+    fn neighbour_joining_small_trees() {
+        assert_eq!(neighbour_joining(&Vec::new(), &[]), ";");
+        assert_eq!(neighbour_joining(&vec![vec![0]], &names(&["A"])), "(A);");
+        assert_eq!(
+            neighbour_joining(&vec![vec![0, 3], vec![3, 0]], &names(&["A", "B"])),
+            "(A:1.5,B:1.5);"
+        );
+        // negative branch length set to 0
+        let distances: DistanceMatrix = vec![vec![0, 1, 5], vec![1, 0, 2], vec![5, 2, 0]];
+        assert_eq!(
+            neighbour_joining(&distances, &names(&["A", "B", "C"])),
+            "(A:2,B:0,C:3);"
+        );
+    }
+
+    #[test]
+    fn newick_labels() {
+        assert_eq!(newick_label("sample_1"), "sample_1");
+        assert_eq!(newick_label("sample 1"), "'sample 1'");
+        assert_eq!(newick_label("it's:1"), "'it''s:1'");
+    }
+}
@@ -1,82 +1,182 @@
 use hashbrown::HashMap;
+use serde::Serialize;
+use ska::ska_dict::bit_encoding::UInt;
 use std::path::PathBuf;
-use std::sync::OnceLock;
 
+use crate::annotation::GeneIndex;
+use crate::distances::{DistanceMatrix, SampleClusters};
+use crate::error::SkaloError;
+use crate::qc::SampleQc;
+
+// integer type encoding the k-mers (2 bits per nucleotide): u64 for k <= 31 and u128 for k <= 63, as in SKA2
+pub trait KmerInt: for<'a> UInt<'a> {}
+impl<IntT: for<'a> UInt<'a>> KmerInt for IntT {}
+
+// variant groups indexed by their (entry, exit) (k-1)-mers
+pub type VariantGroups<IntT> = HashMap<(IntT, IntT), Vec<VariantInfo>>;
+
+// progress messages of the pipeline, only printed if 'verbose' is set (the library is silent by default)
+macro_rules! progress {
+    ($verbose:expr, $($arg:tt)*) => {
+        if $verbose {
+            println!($($arg)*);
+        }
+    };
+}
+pub(crate) use progress;
 
 // structure to hold arguments
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Config {
-    pub input_file: String,
+    pub input_files: Vec<String>,
+    // samples to build the split k-mers from, instead of skf files: (name, FASTA or FASTQ file, 2nd FASTQ file)
+    pub sequence_files: Vec<(String, String, Option<String>)>,
+    // k-mer length (that of the skf file(s) in the command line tool), minimum k-mer count and minimum base quality
+    // (FASTQ) to build the split k-mers
+    pub kmer_length: usize,
+    pub min_count: u16,
+    pub min_qual: u8,
+    // samples to include and to exclude: file listing their names (one per line) or regular expression
+    pub include_samples: Option<String>,
+    pub exclude_samples: Option<String>,
+    // TSV file with the display name and metadata of the samples
+    pub sample_sheet: Option<PathBuf>,
     pub output_name: String,
     pub max_missing: f32,
     pub max_depth: usize,
-    pub	max_indel_kmers: usize,
+    pub max_indel_kmers: usize,
     pub nb_threads: usize,
     pub reference_genome: Option<PathBuf>,
+    // GFF3 or GenBank annotation of the reference genome (gene annotation of the variants)
+    pub annotation_file: Option<PathBuf>,
+    // count positions with missing data in one sample as differences in the SNP distances
+    pub count_missing: bool,
+    // SNP thresholds of the single-linkage clustering of samples (no clustering if empty)
+    pub cluster_thresholds: Vec<usize>,
+    // build a neighbour-joining tree from the SNP distances
+    pub build_tree: bool,
+    // also report each variant group as a single multi-nucleotide/complex variant
+    pub complex_variants: bool,
+    // report samples with several alleles as mixed calls (IUPAC codes and heterozygous genotypes) instead of 'N'
+    pub mixed_calls: bool,
+    // write the graph and the variant groups in GFA format (command line tool)
+    pub export_gfa: bool,
+    // print the progress of the pipeline (set by the command line tool)
+    pub verbose: bool,
+    // full command line (written in the VCF header)
+    pub command_line: String,
 }
-pub static CONFIG: OnceLock<Config> = OnceLock::new();
 
+impl Default for Config {
+    // same default values as the command line
+    fn default() -> Self {
+        Config {
+            input_files: Vec::new(),
+            sequence_files: Vec::new(),
+            kmer_length: 31,
+            min_count: 5,
+            min_qual: 20,
+            include_samples: None,
+            exclude_samples: None,
+            sample_sheet: None,
+            output_name: "skalo".to_string(),
+            max_missing: 0.2,
+            max_depth: 4,
+            max_indel_kmers: 2,
+            nb_threads: 1,
+            reference_genome: None,
+            annotation_file: None,
+            complex_variants: false,
+            mixed_calls: false,
+            count_missing: false,
+            cluster_thresholds: Vec::new(),
+            build_tree: false,
+            export_gfa: false,
+            verbose: false,
+            command_line: String::new(),
+        }
+    }
+}
 
 // structure to hold dataset information
 #[derive(Debug, Clone)]
 pub struct DataInfo {
     pub k_graph: usize,
+    // display names of the samples (original names if no sample sheet)
     pub sample_names: Vec<String>,
+    // names of the samples in the skf file(s) or list of sequence files
+    pub original_names: Vec<String>,
+    // metadata columns of the sample sheet, and their values for each sample (empty if not in the sheet)
+    pub metadata_columns: Vec<String>,
+    pub sample_metadata: Vec<Vec<String>>,
 }
-pub static DATA_INFO: OnceLock<DataInfo> = OnceLock::new();
-
-
-
-
-/*
-pub fn resolve_upac(nucl1: &str, nucl2: &str) -> String {
-    match (nucl1, nucl2) {
-        // Basic nucleotide conflicts
-        ("A", "G") | ("G", "A") => "R".to_string(),
-        ("C", "T") | ("T", "C") => "Y".to_string(),
-        ("G", "C") | ("C", "G") => "S".to_string(),
-        ("A", "T") | ("T", "A") => "W".to_string(),
-        ("G", "T") | ("T", "G") => "K".to_string(),
-        ("A", "C") | ("C", "A") => "M".to_string(),
-
-        // Handling conflicts with existing UPAC codes
-        ("A", "R") | ("R", "A") | ("G", "R") | ("R", "G") => "R".to_string(),
-        ("C", "Y") | ("Y", "C") | ("T", "Y") | ("Y", "T") => "Y".to_string(),
-        ("G", "S") | ("S", "G") | ("C", "S") | ("S", "C") => "S".to_string(),
-        ("A", "W") | ("W", "A") | ("T", "W") | ("W", "T") => "W".to_string(),
-        ("G", "K") | ("K", "G") | ("T", "K") | ("K", "T") => "K".to_string(),
-        ("A", "M") | ("M", "A") | ("C", "M") | ("M", "C") => "M".to_string(),
-
-        // Mixed UPAC code resolution with specific nucleotides
-        ("A", "Y") | ("Y", "A") => "V".to_string(),
-        ("G", "Y") | ("Y", "G") => "B".to_string(),
-        ("T", "R") | ("R", "T") => "D".to_string(),
-        ("C", "R") | ("R", "C") => "V".to_string(),
-
-        // Extended UPAC combinations for three nucleotides
-        ("A", "V") | ("V", "A") | ("C", "V") | ("V", "C") | ("G", "V") | ("V", "G") => "V".to_string(),
-        ("A", "H") | ("H", "A") | ("C", "H") | ("H", "C") | ("T", "H") | ("H", "T") => "H".to_string(),
-        ("A", "D") | ("D", "A") | ("G", "D") | ("D", "G") | ("T", "D") | ("D", "T") => "D".to_string(),
-        ("C", "B") | ("B", "C") | ("G", "B") | ("B", "G") | ("T", "B") | ("B", "T") => "B".to_string(),
-
-        // Default to N for any unresolved conflicts
-        _ => "N".to_string(),
-    }
+
+// structure to hold the variants inferred from the variant groups
+#[derive(Debug, Clone)]
+pub struct VariantCalls {
+    // SNP columns with genomic position (contig index, offset) as key (or counter if no positioning)
+    pub snps: HashMap<(usize, u32), SnpInfo>,
+    pub indels: Vec<IndelInfo>,
+    // multi-nucleotide/complex variants (empty if not requested)
+    pub complex: Vec<ComplexInfo>,
+    // contigs of the reference genome (empty if no positioning)
+    pub contigs: Vec<Contig>,
+    // CDS of the reference genome (empty if no annotation file)
+    pub genes: GeneIndex,
+    pub unpositioned_snps: Vec<UnpositionedSnp>,
+    pub unpositioned_indels: Vec<UnpositionedIndel>,
+    // pairwise SNP distances between samples
+    pub distances: DistanceMatrix,
+    // single-linkage clusters at each SNP threshold
+    pub clusters: Vec<SampleClusters>,
+    // neighbour-joining tree in Newick format (None if not requested)
+    pub tree: Option<String>,
+    // quality metrics of each sample
+    pub sample_qc: Vec<SampleQc>,
+    pub nb_indels: usize,
+    pub snps_not_positioned: usize,
+    pub indels_not_positioned: usize,
+}
+
+// IUPAC code of each set of nucleotides, as a bitmask (A = 1, C = 2, G = 4, T = 8)
+const IUPAC_CODES: [char; 16] = [
+    '-', 'A', 'C', 'M', 'G', 'R', 'S', 'V', 'T', 'W', 'Y', 'H', 'K', 'D', 'B', 'N',
+];
+
+fn nucleotide_mask(nucl: char) -> u8 {
+    IUPAC_CODES
+        .iter()
+        .position(|&code| code == nucl)
+        .unwrap_or(0) as u8
+}
+
+// IUPAC code of the union of 2 nucleotides or IUPAC codes (e.g., 'A' and 'G' -> 'R')
+pub fn resolve_iupac(nucl_1: char, nucl_2: char) -> char {
+    IUPAC_CODES[(nucleotide_mask(nucl_1) | nucleotide_mask(nucl_2)) as usize]
 }
-*/
 
+// nucleotides of a nucleotide or IUPAC code
+pub fn iupac_nucleotides(code: char) -> Vec<char> {
+    let mask = nucleotide_mask(code);
+    ['A', 'C', 'G', 'T']
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| (mask >> i) & 1 == 1)
+        .map(|(_, nucl)| nucl)
+        .collect()
+}
+
+// mixed call (several nucleotides but not 'N') written as an IUPAC code
+pub fn is_mixed_call(code: char) -> bool {
+    code != 'N' && nucleotide_mask(code).count_ones() > 1
+}
 
 pub fn rev_compl(seq: &str) -> String {
-    let reverse_code: HashMap<char, char> = [
-        ('A', 'T'),
-        ('C', 'G'),
-        ('G', 'C'),
-        ('T', 'A'),
-        ('N', 'N'),
-    ]
-    .iter()
-    .cloned()
-    .collect();
+    let reverse_code: HashMap<char, char> =
+        [('A', 'T'), ('C', 'G'), ('G', 'C'), ('T', 'A'), ('N', 'N')]
+            .iter()
+            .cloned()
+            .collect();
 
     let reversed_seq: String = seq.chars().rev().collect();
     let complemented_seq: String = reversed_seq.chars().map(|n| reverse_code[&n]).collect();
@@ -84,36 +184,23 @@ pub fn rev_compl(seq: &str) -> String {
     complemented_seq
 }
 
+pub fn rev_compl_kmer<IntT: KmerInt>(kmer: IntT, k: usize) -> IntT {
+    // complement the nucleotides (A <-> T and C <-> G are bitwise complements)
+    let mut rc_kmer = !kmer;
 
-pub fn rev_compl_u128(kmer: u128, k: usize) -> u128 {
-    // mask for the last 2 bits (representing one nucleotide)
-    let mask = 0b11u128;
-
-    // initialize reverse complement result
-    let mut rc_u128 = 0u128;
-
-    // for each nucleotide in the k-mer
-    for i in 0..k {
-        // get the last 2 bits (current nucleotide)
-        let nucleotide = (kmer >> (2 * i)) & mask;
+    // reverse the order of the nucleotides: bytes, then halves of bytes, then nucleotides within halves of bytes
+    let repeated_byte = IntT::max_value() / IntT::from_encoded_base(0xFF);
+    let mask_4 = repeated_byte * IntT::from_encoded_base(0x0F);
+    let mask_2 = repeated_byte * IntT::from_encoded_base(0x33);
+    rc_kmer = rc_kmer.swap_bytes();
+    rc_kmer = ((rc_kmer >> 4) & mask_4) | ((rc_kmer & mask_4) << 4);
+    rc_kmer = ((rc_kmer >> 2) & mask_2) | ((rc_kmer & mask_2) << 2);
 
-        // complement the nucleotide:
-        let complement = match nucleotide {
-            0b00 => 0b11, // A -> T
-            0b01 => 0b10, // C -> G
-            0b10 => 0b01, // G -> C
-            0b11 => 0b00, // T -> A
-            _ => unreachable!(),
-        };
-
-        // shift the complemented nucleotide to its reverse position
-        rc_u128 |= complement << (2 * (k - i - 1));
-    }
-    rc_u128
+    // the k-mer is now in the highest bits
+    rc_kmer >> (IntT::n_bits() as usize - 2 * k)
 }
 
-
-pub fn encode_kmer(kmer: &str) -> u128 {
+pub fn encode_kmer<IntT: KmerInt>(kmer: &str) -> Result<IntT, SkaloError> {
     let nucleotide_to_bits: [u8; 4] = [
         0b00, // A
         0b01, // C
@@ -121,7 +208,7 @@ pub fn encode_kmer(kmer: &str) -> u128 {
         0b11, // T
     ];
 
-    let mut result: u128 = 0;
+    let mut result = IntT::zero();
 
     for nucleotide in kmer.chars() {
         let index = match nucleotide {
@@ -129,38 +216,36 @@ pub fn encode_kmer(kmer: &str) -> u128 {
             'C' => 1,
             'G' => 2,
             'T' => 3,
-            _ => panic!("Invalid nucleotide"),
+            _ => return Err(SkaloError::InvalidNucleotide(nucleotide)),
         };
-        result = (result << 2) | (nucleotide_to_bits[index] as u128);
+        result = (result << 2) | IntT::from_encoded_base(nucleotide_to_bits[index]);
     }
-    result
+    Ok(result)
 }
 
-
-pub fn encode_u8_kmer(dna: &[u8]) -> u128 {
-    let mut encoded: u128 = 0;
+pub fn encode_u8_kmer<IntT: KmerInt>(dna: &[u8]) -> Result<IntT, SkaloError> {
+    let mut encoded = IntT::zero();
     for &nucleotide in dna {
         encoded <<= 2; // Shift left by 2 bits
-        encoded |= match nucleotide {
+        encoded |= IntT::from_encoded_base(match nucleotide {
             b'A' => 0b00,
             b'C' => 0b01,
             b'G' => 0b10,
             b'T' => 0b11,
-            _ => panic!("Invalid nucleotide: {}", nucleotide as char),
-        };
+            _ => return Err(SkaloError::InvalidNucleotide(nucleotide as char)),
+        });
     }
-    encoded
+    Ok(encoded)
 }
 
-pub fn decode_kmer(encoded: u128, k: usize) -> String {
+pub fn decode_kmer<IntT: KmerInt>(encoded: IntT, k: usize) -> String {
     let bits_to_nucleotide: [char; 4] = ['A', 'C', 'G', 'T'];
     let mut kmer = String::with_capacity(k);
 
-    let mask = (1u128 << (2 * k)) - 1;
-    let mut value = encoded & mask;
+    let mut value = encoded;
 
     for _ in 0..k {
-        let index = (value & 0b11) as usize;
+        let index = (value.lsb_u8() & 0b11) as usize;
         let nucleotide = bits_to_nucleotide[index];
         kmer.insert(0, nucleotide);
         value >>= 2;
@@ -168,11 +253,10 @@ pub fn decode_kmer(encoded: u128, k: usize) -> String {
     kmer
 }
 
-
 // extract last nucleotide from an encoded k-mer
-pub fn get_last_nucl(encoded_kmer: u128) -> char {
+pub fn get_last_nucl<IntT: KmerInt>(encoded_kmer: IntT) -> char {
     // mask the last 2 bits to get the encoded nucleotide
-    let last_bits = (encoded_kmer & 0b11) as u8;
+    let last_bits = encoded_kmer.lsb_u8() & 0b11;
     // decode the nucleotide based on the 2-bit pattern
     match last_bits {
         0b00 => 'A',
@@ -183,7 +267,6 @@ pub fn get_last_nucl(encoded_kmer: u128) -> char {
     }
 }
 
-
 #[derive(Clone)]
 pub struct VariantInfo {
     pub sequence: DnaSequence,
@@ -191,17 +274,93 @@ pub struct VariantInfo {
 }
 
 impl VariantInfo {
-    pub fn new(
-        sequence: DnaSequence,
-        vec_snps: Vec<usize>,
-    ) -> Self {
-        VariantInfo {
-            sequence,
-            vec_snps,
-        }
+    pub fn new(sequence: DnaSequence, vec_snps: Vec<usize>) -> Self {
+        VariantInfo { sequence, vec_snps }
     }
 }
 
+// structure to store a contig of the reference genome
+#[derive(Clone, Debug)]
+pub struct Contig {
+    pub name: String,
+    pub seq: Vec<u8>,
+}
+
+impl Contig {
+    pub fn new(name: String, seq: Vec<u8>) -> Self {
+        Contig { name, seq }
+    }
+}
+
+// structure to store the annotations of a variant (written in the INFO field of the VCF)
+#[derive(Clone, Debug, Default)]
+pub struct VariantAnnotation {
+    // fraction of samples with missing or ambiguous data
+    pub ratio_missing: f32,
+    // number of branches and sequence length of the variant group
+    pub nb_branches: usize,
+    pub group_length: usize,
+    // k-mer votes for the position in the selected orientation and in the opposite one (0 if no positioning)
+    pub position_votes: (usize, usize),
+    // number of other variant groups merged at the same position, and whether their alleles conflict
+    pub nb_merged: usize,
+    pub conflict: bool,
+}
+
+// structure to store a SNP column (one character per sample) and its annotations
+#[derive(Clone, Debug)]
+pub struct SnpInfo {
+    pub column: Vec<char>,
+    pub annotation: VariantAnnotation,
+}
+
+// structure to store a SNP that could not be positioned on the reference genome
+#[derive(Clone, Debug)]
+pub struct UnpositionedSnp {
+    // sequence of the first branch of the variant group and offset of the SNP in it
+    pub flanking_sequence: String,
+    pub offset: usize,
+    // SNP column in the orientation of the flanking sequence
+    pub column: Vec<char>,
+    // "no_hit", "strand_tie" or "position_collision" (conflicting SNP already saved at the same position)
+    pub reason: String,
+}
+
+// structure to store an indel that could not be positioned (or all indels if no reference genome)
+#[derive(Clone, Debug)]
+pub struct UnpositionedIndel {
+    // sequences of both branches of the indel group
+    pub branches: Vec<String>,
+    // allele of each sample ('0' or '1' = index of the branch, 'N' = both, '-' = none)
+    pub column: Vec<char>,
+    // "no_reference", "no_hit", "strand_tie" or "no_reference_allele"
+    pub reason: String,
+}
+
+// structure to store a positioned indel (left-anchored alleles as in VCF)
+#[derive(Clone, Debug)]
+pub struct IndelInfo {
+    pub contig: usize,
+    pub position: u32,
+    pub ref_allele: String,
+    pub alt_allele: String,
+    // '0' (reference allele), '1' (alternative allele), 'm' (both alleles, with mixed calls) or '.' (missing/ambiguous)
+    // per sample
+    pub genotypes: Vec<char>,
+    pub annotation: VariantAnnotation,
+}
+
+// structure to store a positioned multi-nucleotide/complex variant (alleles trimmed to the variable region)
+#[derive(Clone, Debug)]
+pub struct ComplexInfo {
+    pub contig: usize,
+    pub position: u32,
+    pub ref_allele: String,
+    pub alt_alleles: Vec<String>,
+    // allele index per sample (0 = reference allele), None if missing/ambiguous
+    pub genotypes: Vec<Option<usize>>,
+    pub annotation: VariantAnnotation,
+}
 
 /// structure to store DNA sequence in a bit-packed [u8]
 #[derive(Clone)]
@@ -212,10 +371,10 @@ pub struct DnaSequence {
 
 impl DnaSequence {
     // create a new DnaSequence from a DNA string
-    pub fn encode(dna: &str) -> Self {
-        let mut data = Vec::with_capacity((dna.len() + 3) / 4); // 4 nucleotides per byte
+    pub fn encode(dna: &str) -> Result<Self, SkaloError> {
+        let mut data = Vec::with_capacity(dna.len().div_ceil(4)); // 4 nucleotides per byte
         let mut current_byte = 0u8;
-        let mut shift = 6;  // start with the highest 2 bits
+        let mut shift = 6; // start with the highest 2 bits
 
         for (i, nucleotide) in dna.chars().enumerate() {
             let bits = match nucleotide {
@@ -223,36 +382,39 @@ impl DnaSequence {
                 'C' => 0b01,
                 'G' => 0b10,
                 'T' => 0b11,
-                _ => panic!("Invalid nucleotide: {}", nucleotide),
+                _ => return Err(SkaloError::InvalidNucleotide(nucleotide)),
             };
-            current_byte |= bits << shift;  // shift the bits to the correct position
+            current_byte |= bits << shift; // shift the bits to the correct position
 
             if shift == 0 {
                 data.push(current_byte);
                 current_byte = 0;
-                shift = 6;  // reset the shift for the next byte
+                shift = 6; // reset the shift for the next byte
             } else {
                 shift -= 2;
             }
 
             // if we are at the last nucleotide but haven't filled the current byte
             if i == dna.len() - 1 && shift != 6 {
-                data.push(current_byte);  // push the last byte even if partially filled
+                data.push(current_byte); // push the last byte even if partially filled
             }
         }
 
-        DnaSequence { data, original_length: dna.len() }
+        Ok(DnaSequence {
+            data,
+            original_length: dna.len(),
+        })
     }
 
     // decode the bit-packed DnaSequence back into a DNA string
     pub fn decode(&self) -> String {
         let mut dna = String::with_capacity(self.original_length);
-        let mut shift = 6;  // Start with the highest 2 bits
+        let mut shift = 6; // Start with the highest 2 bits
         let mut count = 0;
 
         for &byte in &self.data {
             while shift >= 0 && count < self.original_length {
-                let bits = (byte >> shift) & 0b11;  // Extract 2 bits
+                let bits = (byte >> shift) & 0b11; // Extract 2 bits
                 let nucleotide = match bits {
                     0b00 => 'A',
                     0b01 => 'C',
@@ -262,9 +424,9 @@ impl DnaSequence {
                 };
                 dna.push(nucleotide);
                 count += 1;
-                shift -= 2;  // move to the next 2 bits
+                shift -= 2; // move to the next 2 bits
             }
-            shift = 6;  // reset shift for the next byte
+            shift = 6; // reset shift for the next byte
         }
 
         dna
@@ -275,6 +437,10 @@ impl DnaSequence {
         self.original_length
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.original_length == 0
+    }
+
     pub fn get_range(&self, start: usize, end: usize) -> Vec<u8> {
         let mut nucleotides = Vec::with_capacity(end - start);
 
@@ -296,3 +462,80 @@ impl DnaSequence {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // random DNA sequences of length k (linear congruential generator, for reproducible tests)
+    fn random_kmers(k: usize, nb_kmers: usize) -> Vec<String> {
+        let mut state: u64 = 42;
+        (0..nb_kmers)
+            .map(|_| {
+                (0..k)
+                    .map(|_| {
+                        state = state
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        ['A', 'C', 'G', 'T'][(state >> 62) as usize]
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // reverse-complement of encoded k-mers compared with that of their sequence
+    fn check_rev_compl_kmer<IntT: KmerInt + std::fmt::Debug>(k: usize) {
+        for kmer in random_kmers(k, 200)
+            .iter()
+            .chain([&"A".repeat(k), &"T".repeat(k)])
+        {
+            let encoded: IntT = encode_kmer(kmer).unwrap();
+            let expected: IntT = encode_kmer(&rev_compl(kmer)).unwrap();
+            assert_eq!(rev_compl_kmer(encoded, k), expected, "k = {}, {}", k, kmer);
+            assert_eq!(rev_compl_kmer(rev_compl_kmer(encoded, k), k), encoded);
+        }
+    }
+
+    #[test]
+    fn rev_compl_kmer_u64() {
+        for k in [1, 5, 31, 32] {
+            check_rev_compl_kmer::<u64>(k);
+        }
+    }
+
+    #[test]
+    fn rev_compl_kmer_u128() {
+        for k in [1, 5, 31, 63, 64] {
+            check_rev_compl_kmer::<u128>(k);
+        }
+    }
+
+    #[test]
+    fn resolve_iupac_codes() {
+        assert_eq!(resolve_iupac('A', 'G'), 'R');
+        assert_eq!(resolve_iupac('C', 'T'), 'Y');
+        assert_eq!(resolve_iupac('A', 'A'), 'A');
+        // union of a code and a nucleotide, and of 2 codes
+        assert_eq!(resolve_iupac('R', 'C'), 'V');
+        assert_eq!(resolve_iupac('M', 'K'), 'N');
+        assert_eq!(resolve_iupac('R', 'N'), 'N');
+    }
+
+    #[test]
+    fn iupac_nucleotides_of_codes() {
+        assert_eq!(iupac_nucleotides('A'), vec!['A']);
+        assert_eq!(iupac_nucleotides('R'), vec!['A', 'G']);
+        assert_eq!(iupac_nucleotides('B'), vec!['C', 'G', 'T']);
+        assert_eq!(iupac_nucleotides('N'), vec!['A', 'C', 'G', 'T']);
+        assert!(iupac_nucleotides('-').is_empty());
+    }
+
+    #[test]
+    fn mixed_calls() {
+        assert!(is_mixed_call('R'));
+        assert!(is_mixed_call('H'));
+        assert!(!is_mixed_call('A'));
+        assert!(!is_mixed_call('N'));
+        assert!(!is_mixed_call('-'));
+    }
+}